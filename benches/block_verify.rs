@@ -0,0 +1,74 @@
+//! Compares verifying the signatures of a block one record at a time with the batched
+//! `Block::verify()` and with `ValidationPool::verify_block()`.
+//!
+//! Run with `cargo bench --bench block_verify`.
+
+use std::time::{Duration, Instant};
+
+use blockchain::{
+    blockchain::{Block, Genesis, Record, ValidationPool},
+    gen::{self, HashAlgorithm},
+    utils::Transaction,
+};
+
+const BLOCK_SIZES: [usize; 3] = [10, 1_000, 10_000];
+
+fn main() {
+    let chain_id = Genesis::new("bench").chain_id();
+    let key_pairs: Vec<_> = (0..16).map(|_| gen::generate_key_pair()).collect();
+    let pool = ValidationPool::new(0).unwrap();
+
+    println!(
+        "{:>8}  {:>14}  {:>14}  {:>14}",
+        "records",
+        "one by one",
+        "batched",
+        format!("{} threads", pool.threads())
+    );
+    for size in BLOCK_SIZES {
+        let signed_records = (0..size)
+            .map(|i| {
+                let key_pair = &key_pairs[i % key_pairs.len()];
+                Transaction::new("A", "B", &i.to_string())
+                    .sign(
+                        key_pair.private_key(),
+                        &chain_id,
+                        (i / key_pairs.len()) as u64,
+                    )
+                    .unwrap()
+            })
+            .collect();
+        let block: Block<Transaction> = Block { signed_records };
+        let header = block.header(None, HashAlgorithm::default()).unwrap();
+
+        // The other measures also check the header, which is cheap next to the signatures
+        let one_by_one = time(size, || {
+            for signed_record in block.get_signed_records() {
+                signed_record.verify().unwrap();
+            }
+        });
+        let batched = time(size, || {
+            block.verify(&header, HashAlgorithm::default()).unwrap();
+        });
+        let parallel = time(size, || {
+            pool.verify_block(&block, &header, HashAlgorithm::default())
+                .unwrap();
+        });
+
+        println!(
+            "{:>8}  {:>14.3?}  {:>14.3?}  {:>14.3?}",
+            size, one_by_one, batched, parallel
+        );
+    }
+}
+
+/// Average duration of `run` over enough iterations to verify about 20k records
+fn time(size: usize, mut run: impl FnMut()) -> Duration {
+    let iterations = (20_000 / size).max(3);
+    run();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run();
+    }
+    start.elapsed() / iterations as u32
+}
@@ -0,0 +1,318 @@
+use crate::{
+    consensus::Consensus,
+    errs::CustomErrs,
+    io::{Database2, QueryRange},
+    utils::InMemoryDB,
+};
+
+use super::{record_verdicts, Block, BlockChain, PublishedBlock, Record, SignedRecord};
+
+/// Controls how far `BlockChain::verify_chain` goes once it finds a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditMode {
+    /// Stop at the first inconsistency
+    FirstIssue,
+    /// Walk the whole chain and report every inconsistency
+    AllIssues,
+}
+
+/// A single inconsistency found while auditing the chain.
+///
+/// Heights refer to rows of the `BLOCKCHAIN` table, indexes to the position of a
+/// record within its block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainIssue {
+    /// The `BLOCKCHAIN` row at this height is missing or cannot be decoded
+    MissingBlock { height: i64 },
+    /// The first block is not the genesis block this chain was opened with
+    GenesisMismatch,
+    /// The height stored in the header does not match the row's position
+    HeightMismatch { height: i64, stored: u64 },
+    /// `previous_hash` does not point at the block below
+    BrokenLink { height: i64 },
+    /// The stored hash is not the hash of the stored header
+    HashMismatch { height: i64 },
+    /// The header is rejected by the chain's consensus engine
+    ConsensusViolation { height: i64, reason: CustomErrs },
+    /// The block's range starts before the previous block's range ends
+    OverlappingRange { height: i64, range: QueryRange },
+    /// The block's range leaves records between it and the previous block
+    GappedRange { height: i64, range: QueryRange },
+    /// The header's record count does not match the size of its range
+    RecordCountMismatch { height: i64 },
+    /// Some `RECORDCHAIN` rows referenced by the block do not exist
+    MissingRecords { height: i64, range: QueryRange },
+    /// A `RECORDCHAIN` row referenced by the block cannot be decoded
+    CorruptedRecord { height: i64, index: usize },
+    /// A record's signature does not verify
+    BadSignature {
+        height: i64,
+        index: usize,
+        reason: CustomErrs,
+    },
+    /// A record fails `Record::validate()` or `Record::validate_against()`
+    RejectedRecord {
+        height: i64,
+        index: usize,
+        reason: CustomErrs,
+    },
+    /// A record's nonce replays or skips one of its signer's nonces
+    NonceViolation {
+        height: i64,
+        index: usize,
+        reason: CustomErrs,
+    },
+    /// The recomputed records root does not match the header
+    RecordsRootMismatch { height: i64 },
+    /// `RECORDCHAIN` rows exist past the end of the last block
+    UnreferencedRecords { range: QueryRange },
+}
+
+/// Outcome of `BlockChain::verify_chain`
+#[derive(Debug, Clone, Default)]
+pub struct ChainReport {
+    /// Number of `BLOCKCHAIN` rows that were examined
+    pub blocks_checked: i64,
+    pub issues: Vec<ChainIssue>,
+}
+
+impl ChainReport {
+    /// `true` if no inconsistency was found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn first_issue(&self) -> Option<&ChainIssue> {
+        self.issues.first()
+    }
+}
+
+impl<D: Database2, C: Consensus> BlockChain<D, C> {
+    /// Walks every block on the chain and checks that the stored data is consistent.
+    ///
+    /// Every `BLOCKCHAIN` row is reloaded with its `RECORDCHAIN` range, every signature is
+    /// re-verified and every hash recomputed, on the validation pool if the chain has one.
+    /// The returned report lists the inconsistencies found; `Err` is only returned if the
+    /// database itself cannot be read.
+    ///
+    /// To run `Record::validate_against()` on each record against the chain as it stood
+    /// before its block, as `push()` did, the blocks are replayed one by one onto a copy of
+    /// the chain held in memory.
+    pub fn verify_chain<R: Record>(&self, mode: AuditMode) -> Result<ChainReport, CustomErrs> {
+        let mut report = ChainReport::default();
+        let blocks = self
+            .database
+            .size_of_table::<&PublishedBlock>()?
+            .unwrap_or(0);
+        let records = self.database.size_of_table::<&Block<R>>()?.unwrap_or(0);
+
+        let mut parent: Option<PublishedBlock> = None;
+        let mut next_record = 0;
+        let mut replay = BlockChain::open(InMemoryDB::new(), self.genesis.clone())?;
+
+        for height in 0..blocks {
+            report.blocks_checked += 1;
+
+            let published_block = match self.get_published_block_at(height) {
+                Ok(published_block) => published_block,
+                Err(_) => {
+                    report.issues.push(ChainIssue::MissingBlock { height });
+                    if mode == AuditMode::FirstIssue {
+                        return Ok(report);
+                    }
+                    parent = None;
+                    continue;
+                }
+            };
+
+            let issues = self.audit_block::<R>(
+                height,
+                &published_block,
+                parent.as_ref(),
+                next_record,
+                &mut replay,
+            )?;
+            report.issues.extend(issues);
+
+            if mode == AuditMode::FirstIssue && !report.is_valid() {
+                report.issues.truncate(1);
+                return Ok(report);
+            }
+
+            let range = published_block.block_position;
+            next_record = next_record.max(range.end + 1);
+            parent = Some(published_block);
+        }
+
+        if records > next_record {
+            report.issues.push(ChainIssue::UnreferencedRecords {
+                range: QueryRange::new(next_record, records - 1),
+            });
+        }
+
+        Ok(report)
+    }
+
+    fn audit_block<R: Record>(
+        &self,
+        height: i64,
+        published_block: &PublishedBlock,
+        parent: Option<&PublishedBlock>,
+        next_record: i64,
+        replay: &mut BlockChain<InMemoryDB>,
+    ) -> Result<Vec<ChainIssue>, CustomErrs> {
+        let mut issues = vec![];
+        let header = &published_block.header;
+        let range = published_block.block_position;
+
+        if height == 0 {
+            if published_block.hash != self.genesis.hash()
+                || *header != self.genesis.header()
+                || !range.is_empty()
+            {
+                issues.push(ChainIssue::GenesisMismatch);
+            }
+            return Ok(issues);
+        }
+
+        if header.height != height as u64 {
+            issues.push(ChainIssue::HeightMismatch {
+                height,
+                stored: header.height,
+            });
+        }
+
+        if parent.map(|parent| &parent.hash) != Some(&header.previous_hash) {
+            issues.push(ChainIssue::BrokenLink { height });
+        }
+
+        if let Some(parent) = parent {
+            if let Err(reason) = self.consensus.validate(header, &parent.header, self) {
+                issues.push(ChainIssue::ConsensusViolation { height, reason });
+            }
+        }
+
+        if published_block.hash != header.hash(self.hash_algorithm()) {
+            issues.push(ChainIssue::HashMismatch { height });
+        }
+
+        if range.begin < next_record {
+            issues.push(ChainIssue::OverlappingRange { height, range });
+        } else if range.begin > next_record {
+            issues.push(ChainIssue::GappedRange { height, range });
+        }
+
+        if range.len() as u64 != header.record_count {
+            issues.push(ChainIssue::RecordCountMismatch { height });
+        }
+
+        let rows = self.database.get_rows::<&Block<R>>(range)?;
+        if (rows.len() as i64) < range.len() {
+            issues.push(ChainIssue::MissingRecords { height, range });
+            replay.replay::<R>(
+                published_block,
+                &Block {
+                    signed_records: vec![],
+                },
+            )?;
+            return Ok(issues);
+        }
+
+        let mut signed_records = Vec::with_capacity(rows.len());
+        let mut corrupted = vec![false; rows.len()];
+        for (index, row) in rows.iter().enumerate() {
+            match SignedRecord::<R>::from_vec(row) {
+                Ok(signed_record) => signed_records.push(signed_record),
+                Err(_) => corrupted[index] = true,
+            }
+        }
+
+        let chain_id = self.chain_id();
+        let verdicts = match &self.validation_pool {
+            Some(pool) => pool.record_verdicts(&signed_records, &chain_id),
+            None => record_verdicts(&signed_records, &chain_id),
+        };
+        // Against the chain before this block: nonces are only accepted further down
+        let checks: Vec<_> = signed_records
+            .iter()
+            .enumerate()
+            .map(|(index, signed_record)| {
+                signed_record.get_record().validate_against(
+                    signed_record.get_signer(),
+                    replay,
+                    &signed_records[..index],
+                )
+            })
+            .collect();
+        let mut decoded = signed_records.iter().zip(verdicts).zip(checks);
+
+        for (index, corrupted) in corrupted.into_iter().enumerate() {
+            if corrupted {
+                issues.push(ChainIssue::CorruptedRecord { height, index });
+                continue;
+            }
+
+            let ((signed_record, verdict), check) = decoded.next().unwrap();
+            if let Err(reason) = verdict {
+                issues.push(ChainIssue::BadSignature {
+                    height,
+                    index,
+                    reason,
+                });
+            }
+            if let Err(reason) = signed_record.get_record().validate().and(check) {
+                issues.push(ChainIssue::RejectedRecord {
+                    height,
+                    index,
+                    reason,
+                });
+            }
+            if let Err(reason) = replay
+                .nonces
+                .accept(signed_record.get_signer(), signed_record.nonce)
+            {
+                issues.push(ChainIssue::NonceViolation {
+                    height,
+                    index,
+                    reason,
+                });
+            }
+        }
+
+        let block = Block { signed_records };
+        if block.signed_records.len() == rows.len() {
+            let records_root = match &self.validation_pool {
+                Some(pool) => pool.records_root(&block, self.hash_algorithm()),
+                None => block.records_root(self.hash_algorithm()),
+            }?;
+            if records_root != header.records_root {
+                issues.push(ChainIssue::RecordsRootMismatch { height });
+            }
+        }
+        replay.replay(published_block, &block)?;
+
+        Ok(issues)
+    }
+}
+
+impl BlockChain<InMemoryDB> {
+    /// Places an audited block on this copy of the chain as it is stored, whatever issues
+    /// were found in it, so that the next block is checked against the chain `push()` saw.
+    ///
+    /// Only the records of `block` that could be decoded are copied.
+    fn replay<R: Record>(
+        &mut self,
+        published_block: &PublishedBlock,
+        block: &Block<R>,
+    ) -> Result<(), CustomErrs> {
+        self.database.transaction(|database| {
+            let block_position = database.insert(&block)?;
+            let published_block = PublishedBlock {
+                block_position,
+                ..published_block.clone()
+            };
+            database.insert(&&published_block)
+        })?;
+        Ok(())
+    }
+}
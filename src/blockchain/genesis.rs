@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errs::CustomErrs,
+    gen::{Hash, HashAlgorithm, PublicKey},
+    io::DatabaseInsertable,
+};
+
+use super::{column_count_mismatch, from_column, to_column, BlockHeader};
+
+static GENESIS_COLUMNS: [&str; 2] = ["ChainId", "Genesis"];
+static GENESIS: &str = "GENESIS";
+
+/// Description of the first block of a chain.
+///
+/// Every field is committed to by the genesis hash, which doubles as the chain ID,
+/// so two chains opened with different `Genesis` values can never be confused.
+/// Build it deterministically: a chain must be reopened with exactly the same value.
+///
+/// # Example
+/// ```
+/// use blockchain::blockchain::Genesis;
+///
+/// let genesis = Genesis::new("ledger").with_created_at(1_676_000_000_000);
+/// assert_eq!(genesis.chain_id(), Genesis::new("ledger").with_created_at(1_676_000_000_000).chain_id());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis {
+    pub chain_name: String,
+    /// Milliseconds since the unix epoch, used as the genesis header's timestamp
+    pub created_at: u64,
+    /// Arbitrary application data anchored in the genesis block
+    pub payload: Vec<u8>,
+    /// Public keys given authority over the chain from the start, if any
+    pub authorities: Vec<PublicKey>,
+    /// Algorithm every block, record root and proof of the chain is hashed with
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Genesis {
+    pub fn new(chain_name: &str) -> Self {
+        Self {
+            chain_name: chain_name.to_owned(),
+            created_at: 0,
+            payload: vec![],
+            authorities: vec![],
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    pub fn with_created_at(mut self, created_at: u64) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn with_authorities(mut self, authorities: Vec<PublicKey>) -> Self {
+        self.authorities = authorities;
+        self
+    }
+
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Header of the genesis block. It holds no records; its records root commits to `self`.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            previous_hash: BlockHeader::null_hash(self.hash_algorithm),
+            height: 0,
+            timestamp: self.created_at,
+            record_count: 0,
+            records_root: self
+                .hash_algorithm
+                .encrypt(self)
+                .expect("a genesis always serializes"),
+            difficulty: 0,
+            nonce: 0,
+            sealer: None,
+            seal: None,
+            governance: None,
+        }
+    }
+
+    /// Hash of the genesis block
+    pub fn hash(&self) -> Hash {
+        self.header().hash(self.hash_algorithm)
+    }
+
+    /// Identifier of the chain started by this genesis block, equal to its hash
+    pub fn chain_id(&self) -> Hash {
+        self.hash()
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<String>, CustomErrs> {
+        Ok(vec![self.chain_id().to_string(), to_column(self)?])
+    }
+
+    /// Rebuilds a `Genesis` from a row produced by `to_vec()`
+    pub fn from_vec(row: &[String]) -> Result<Self, CustomErrs> {
+        match row {
+            [_, genesis] => from_column(GENESIS, genesis),
+            _ => Err(column_count_mismatch(GENESIS, &GENESIS_COLUMNS, row)),
+        }
+    }
+}
+
+impl IntoIterator for &Genesis {
+    type Item = Result<Vec<String>, CustomErrs>;
+
+    type IntoIter = std::iter::Once<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        std::iter::once(self.to_vec())
+    }
+}
+
+impl DatabaseInsertable for &Genesis {
+    fn get_name() -> &'static str {
+        GENESIS
+    }
+
+    fn columns() -> &'static [&'static str] {
+        &GENESIS_COLUMNS
+    }
+
+    fn len(&self) -> i64 {
+        1
+    }
+}
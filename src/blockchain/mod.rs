@@ -1,116 +1,287 @@
-use serde::{Deserialize, Serialize};
+mod audit;
+mod genesis;
+mod multisig;
+mod nonces;
+mod parallel;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
+    consensus::{ChainView, Consensus, GovernanceRecord, NoConsensus},
     errs::CustomErrs,
     gen,
-    gen::Hash,
+    gen::{
+        merkle::{MerkleProof, MerkleTree},
+        Hash, HashAlgorithm, PrivateKey, PublicKey, Signature, HASH_LEN,
+    },
     io::{Database2, DatabaseInsertable, QueryRange},
 };
 
-///
-///
-///
-///
-///
-///
-///
+pub use audit::{AuditMode, ChainIssue, ChainReport};
+pub use genesis::Genesis;
+pub use multisig::{MultiSigProposal, Signer, SignerSet, MAX_SIGNERS};
+use nonces::NonceTracker;
+pub use parallel::ValidationPool;
+
+static RECORDS_COLUMNS: [&str; 6] = [
+    "Record",
+    "Identity",
+    "Signature",
+    "Nonce",
+    "ChainId",
+    "Version",
+];
+static BLOCKS_COLUMNS: [&str; 12] = [
+    "Hash",
+    "PreviousHash",
+    "Height",
+    "Timestamp",
+    "RecordCount",
+    "RecordsRoot",
+    "Difficulty",
+    "Nonce",
+    "Sealer",
+    "Seal",
+    "Governance",
+    "Range",
+];
+static RECORDS: &str = "RECORDCHAIN";
+static BLOCKS: &str = "BLOCKCHAIN";
 
-static RECORDS_COLUMNS: [&'static str; 3] = ["Record", "Identity", "Signature"];
-static BLOCKS_COLUMNS: [&'static str; 2] = ["Hash", "Range"];
-static RECORDS: &'static str = "RECORDCHAIN";
-static BLOCKS: &'static str = "BLOCKCHAIN";
+/// Tag starting every record signing payload, so that a record signature is never valid
+/// for any other signed data, e.g. a block seal
+pub const SIGNING_DOMAIN: &str = "blockchain/signed-record";
+/// Tag starting the signing payload of a record signed by a `SignerSet`
+pub const MULTI_SIGNING_DOMAIN: &str = "blockchain/multi-signed-record";
+/// Version of the signing payload built by `SignedRecord::signing_payload()`
+pub const SIGNING_VERSION: u8 = 1;
+/// Default of how far ahead of the local clock a header's timestamp may be, in
+/// milliseconds, see `BlockChain::with_max_clock_drift()`
+pub const DEFAULT_MAX_CLOCK_DRIFT: u64 = 2 * 60 * 60 * 1000;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(bound = "T: Record")]
 pub struct SignedRecord<T: Record> {
-    pub public_key: Vec<u8>,
-    pub signature: Vec<u8>,
+    pub signer: Signer,
+    /// Signatures over the signing payload, each with the index of its key in `signer`,
+    /// in increasing index order. A `Signer::Key` has a single one, at index 0.
+    pub signatures: Vec<(u8, Signature)>,
+    /// Version of the payload the signatures were computed over, see `SIGNING_VERSION`
+    pub version: u8,
+    /// Chain the record was signed for, see `BlockChain::chain_id()`
+    pub chain_id: Hash,
+    /// Position of this record among the records signed by `signer`.
+    ///
+    /// A chain accepts a signer's records only with nonces 0, 1, 2, ... in order, so a
+    /// published record can never be replayed.
+    pub nonce: u64,
     pub record: T,
 }
 
 impl<T: Record> SignedRecord<T> {
+    /// Checks that enough keys of the signer signed the record, see `Signer::check()`
     pub fn verify(&self) -> Result<(), CustomErrs> {
-        let msg = bincode::serialize(&self.record).unwrap();
-        gen::verify_signature(&self.public_key, &msg, &self.signature)
+        self.verify_payload(&self.payload()?)
+    }
+
+    /// Checks the signature and that the record was signed for the chain `chain_id`
+    pub fn verify_for_chain(&self, chain_id: &Hash) -> Result<(), CustomErrs> {
+        if self.chain_id != *chain_id {
+            return Err(CustomErrs::WrongChain {
+                expected: *chain_id,
+                found: self.chain_id,
+            });
+        }
+        self.verify()
+    }
+
+    /// Bytes signed for `record`, as the `nonce`-th record of `signer` on `chain_id`.
+    ///
+    /// For a `Signer::Key`, the payload is the bincode encoding of `SIGNING_DOMAIN`,
+    /// `version`, `chain_id`, `T::RECORD_TYPE`, `nonce` and `record`, in this order. For a
+    /// `Signer::Set`, it is the encoding of `MULTI_SIGNING_DOMAIN`, `version`, `chain_id`,
+    /// `T::RECORD_TYPE`, the `SignerSet`, `nonce` and `record`, so that the signature of
+    /// one key of a set is never valid for a record of that key alone or of another set.
+    /// Only `SIGNING_VERSION` is supported.
+    pub fn signing_payload(
+        version: u8,
+        signer: &Signer,
+        chain_id: &Hash,
+        nonce: u64,
+        record: &T,
+    ) -> Result<Vec<u8>, CustomErrs> {
+        if version != SIGNING_VERSION {
+            return Err(CustomErrs::UnsupportedSigningVersion { found: version });
+        }
+
+        match signer {
+            Signer::Key(_) => bincode::serialize(&(
+                SIGNING_DOMAIN,
+                version,
+                chain_id,
+                T::RECORD_TYPE,
+                nonce,
+                record,
+            )),
+            Signer::Set(signer_set) => bincode::serialize(&(
+                MULTI_SIGNING_DOMAIN,
+                version,
+                chain_id,
+                T::RECORD_TYPE,
+                signer_set,
+                nonce,
+                record,
+            )),
+        }
+        .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })
+    }
+
+    /// Bytes the signatures were computed over
+    fn payload(&self) -> Result<Vec<u8>, CustomErrs> {
+        Self::signing_payload(
+            self.version,
+            &self.signer,
+            &self.chain_id,
+            self.nonce,
+            &self.record,
+        )
     }
 
-    fn is_valid(&self) -> bool {
-        self.verify().is_ok()
+    /// `verify()` against the already computed `payload()`
+    fn verify_payload(&self, payload: &[u8]) -> Result<(), CustomErrs> {
+        self.signer.check(&self.signatures)?;
+        for (index, signature) in &self.signatures {
+            // `check()` made sure every index names a key
+            let public_key = self.signer.key_at(*index).unwrap();
+            gen::verify_signature(public_key, payload, signature)?;
+        }
+        Ok(())
     }
 
-    pub fn to_vec(&self) -> Vec<String> {
-        vec![
-            serde_json::to_string(&self.record).unwrap(),
-            serde_json::to_string(&self.public_key).unwrap(),
-            serde_json::to_string(&self.signature).unwrap(),
-        ]
+    /// Identifies this record, e.g. in the `MemPool`
+    pub fn hash(&self) -> Result<Hash, CustomErrs> {
+        gen::encrypt(self)
     }
 
-    pub fn get_signature(&self) -> &[u8] {
-        &self.signature
+    pub fn to_vec(&self) -> Result<Vec<String>, CustomErrs> {
+        Ok(vec![
+            to_column(&self.record)?,
+            to_column(&self.signer)?,
+            to_column(&self.signatures)?,
+            self.nonce.to_string(),
+            self.chain_id.to_string(),
+            self.version.to_string(),
+        ])
+    }
+
+    /// Rebuilds a `SignedRecord` from a row produced by `to_vec()`
+    pub fn from_vec(row: &[String]) -> Result<Self, CustomErrs> {
+        match row {
+            [record, signer, signatures, nonce, chain_id, version] => Ok(Self {
+                record: from_column(RECORDS, record)?,
+                signer: from_column(RECORDS, signer)?,
+                signatures: from_column(RECORDS, signatures)?,
+                nonce: from_column(RECORDS, nonce)?,
+                chain_id: hash_column(RECORDS, chain_id)?,
+                version: from_column(RECORDS, version)?,
+            }),
+            _ => Err(column_count_mismatch(RECORDS, &RECORDS_COLUMNS, row)),
+        }
+    }
+
+    pub fn get_signatures(&self) -> &[(u8, Signature)] {
+        &self.signatures
     }
 
     pub fn get_record(&self) -> &T {
         &self.record
     }
 
-    pub fn get_signer(&self) -> &Vec<u8> {
-        &self.public_key
+    pub fn get_signer(&self) -> &Signer {
+        &self.signer
     }
-}
 
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
+    pub fn get_nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn get_chain_id(&self) -> Hash {
+        self.chain_id
+    }
+}
 
 pub trait Record
 where
-    Self: Clone + Sized + Serialize + for<'a> Deserialize<'a>,
+    Self: Clone + Sized + Send + Sync + Serialize + for<'a> Deserialize<'a>,
 {
+    /// Name of this record type, part of every signature over it.
+    ///
+    /// A signature made for one record type is thus never valid for another type with the
+    /// same byte layout. Every record type used on a chain needs its own name.
+    const RECORD_TYPE: &'static str;
+
+    /// Checks that this record is well formed, whatever the state of the chain, e.g. that
+    /// an amount is a number.
+    ///
+    /// `Block::verify()`, `MemPool::admit()` and `BlockChain::verify_chain()` reject records
+    /// failing it, see `CustomErrs::RecordRejected`. Every record is accepted by default.
+    fn validate(&self) -> Result<(), CustomErrs> {
+        Ok(())
+    }
+
+    /// Checks this record, signed by `signer`, against `chain`, e.g. that the sender of a
+    /// transfer can afford it.
+    ///
+    /// `BlockChain::push()` calls it for each record of a block before writing the block,
+    /// with `chain` as it stands before the block and `preceding` the records placed before
+    /// this one in the block. `BlockChain::verify_chain()` calls it the same way on a copy of
+    /// the chain replayed up to the block. `MemPool::admit_against()` calls it with no
+    /// preceding records.
+    /// Every record is accepted by default.
+    fn validate_against<D: Database2, C: Consensus>(
+        &self,
+        _signer: &Signer,
+        _chain: &BlockChain<D, C>,
+        _preceding: &[SignedRecord<Self>],
+    ) -> Result<(), CustomErrs> {
+        Ok(())
+    }
+
+    /// Signs this record as the `nonce`-th record of the owner of `private_key` on the
+    /// chain `chain_id`, see `BlockChain::chain_id()` and `BlockChain::next_nonce()`.
+    ///
+    /// The signer's public key is derived from `private_key`. Records of a `SignerSet` are
+    /// signed through a `MultiSigProposal` instead.
     fn sign(
         &self,
-        private_key: &[u8],
-        public_key: &[u8],
+        private_key: &PrivateKey,
+        chain_id: &Hash,
+        nonce: u64,
     ) -> Result<SignedRecord<Self>, CustomErrs> {
-        let msg = bincode::serialize(self).unwrap();
-        let signature = gen::sign(&msg, private_key)?;
-        let public_key = public_key.to_vec();
+        let signer = Signer::Key(private_key.public_key());
+        let msg = SignedRecord::signing_payload(SIGNING_VERSION, &signer, chain_id, nonce, self)?;
+        let signature = gen::sign(&msg, private_key);
         Ok(SignedRecord {
-            public_key,
-            signature,
+            signer,
+            signatures: vec![(0, signature)],
+            version: SIGNING_VERSION,
+            chain_id: *chain_id,
+            nonce,
             record: self.clone(),
         })
     }
 }
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-///
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(bound = "R: Record")]
 pub struct Block<R: Record> {
     pub signed_records: Vec<SignedRecord<R>>,
 }
 
 impl<R: Record> DatabaseInsertable for &Block<R> {
     fn get_name() -> &'static str {
-        &RECORDS
+        RECORDS
     }
 
     fn columns() -> &'static [&'static str] {
@@ -128,7 +299,7 @@ pub struct IterBlockString<'a, R: Record> {
 }
 
 impl<'a, R: Record> Iterator for IterBlockString<'a, R> {
-    type Item = Vec<String>;
+    type Item = Result<Vec<String>, CustomErrs>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.pos < self.records.len() {
@@ -141,7 +312,7 @@ impl<'a, R: Record> Iterator for IterBlockString<'a, R> {
 }
 
 impl<'a, R: Record> IntoIterator for &'a Block<R> {
-    type Item = Vec<String>;
+    type Item = Result<Vec<String>, CustomErrs>;
 
     type IntoIter = IterBlockString<'a, R>;
 
@@ -175,29 +346,255 @@ impl<R: Record> Block<R> {
         &self.signed_records
     }
 
-    pub fn verify(&self) -> Result<VerifiedBlock<R>, CustomErrs> {
-        if self.signed_records.iter().all(|r| r.is_valid()) {
-            let hash = gen::encrypt(&self);
-            Ok(VerifiedBlock {
-                block: self.clone(),
-                hash: hash.to_vec(),
+    /// Merkle tree whose leaves are the records of this block, in order
+    pub fn merkle_tree(&self, algorithm: HashAlgorithm) -> Result<MerkleTree, CustomErrs> {
+        MerkleTree::from_items(algorithm, &self.signed_records)
+    }
+
+    /// Merkle root committing to every record of this block, in order
+    pub fn records_root(&self, algorithm: HashAlgorithm) -> Result<Hash, CustomErrs> {
+        Ok(self.merkle_tree(algorithm)?.root())
+    }
+
+    /// Proof that the record at `index` is part of this block, checked against `records_root()`
+    pub fn proof(
+        &self,
+        algorithm: HashAlgorithm,
+        index: usize,
+    ) -> Result<Option<MerkleProof>, CustomErrs> {
+        Ok(self.merkle_tree(algorithm)?.proof(index))
+    }
+
+    /// Builds the header this block would carry if it were placed on top of `parent`,
+    /// on a chain hashed with `algorithm`.
+    ///
+    /// `None` as parent means this block is the first one on the chain.
+    pub fn header(
+        &self,
+        parent: Option<&PublishedBlock>,
+        algorithm: HashAlgorithm,
+    ) -> Result<BlockHeader, CustomErrs> {
+        let (previous_hash, height) = match parent {
+            Some(parent) => (parent.hash, parent.header.height + 1),
+            None => (BlockHeader::null_hash(algorithm), 0),
+        };
+
+        Ok(BlockHeader {
+            previous_hash,
+            height,
+            timestamp: now_millis(),
+            record_count: self.signed_records.len() as u64,
+            records_root: self.records_root(algorithm)?,
+            difficulty: 0,
+            nonce: 0,
+            sealer: None,
+            seal: None,
+            governance: None,
+        })
+    }
+
+    /// Checks every signature in this block, that every record passes `Record::validate()`
+    /// and that `header` describes exactly these records, hashing with `algorithm`.
+    ///
+    /// `ValidationPool::verify_block()` gives the same verdict using several threads.
+    pub fn verify(
+        &self,
+        header: &BlockHeader,
+        algorithm: HashAlgorithm,
+    ) -> Result<VerifiedBlock<R>, CustomErrs> {
+        let payloads = self.signing_payloads()?;
+        verify_signatures(&self.signed_records, &payloads, 0)?;
+        self.validate_records()?;
+        self.check_header(header, &self.records_root(algorithm)?)?;
+        Ok(self.verified(header, algorithm))
+    }
+
+    /// Payload signed by each record, see `SignedRecord::signing_payload()`
+    fn signing_payloads(&self) -> Result<Vec<Vec<u8>>, CustomErrs> {
+        self.signed_records
+            .iter()
+            .enumerate()
+            .map(|(index, signed_record)| {
+                signed_record.payload().map_err(|err| err.in_record(index))
             })
-        } else {
-            Err(CustomErrs::InvalidBlock)
+            .collect()
+    }
+
+    /// Runs `Record::validate()` on every record, in order
+    fn validate_records(&self) -> Result<(), CustomErrs> {
+        for (index, signed_record) in self.signed_records.iter().enumerate() {
+            signed_record
+                .record
+                .validate()
+                .map_err(|err| err.in_record(index))?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `header` describes this block, whose records root is `records_root`
+    fn check_header(&self, header: &BlockHeader, records_root: &Hash) -> Result<(), CustomErrs> {
+        if header.record_count != self.signed_records.len() as u64 {
+            return Err(CustomErrs::RecordCountMismatch {
+                expected: self.signed_records.len() as u64,
+                found: header.record_count,
+            });
+        }
+
+        if header.records_root != *records_root {
+            return Err(CustomErrs::RecordsRootMismatch);
+        }
+        Ok(())
+    }
+
+    fn verified(&self, header: &BlockHeader, algorithm: HashAlgorithm) -> VerifiedBlock<R> {
+        VerifiedBlock {
+            hash: header.hash(algorithm),
+            header: header.clone(),
+            block: self.clone(),
         }
     }
 }
 
+/// Checks the signatures of `signed_records` over `payloads` in one batch.
+///
+/// Only when the batch fails are the records checked one by one, to report the index of
+/// the first invalid one; `first_index` is the index of `signed_records[0]` in its block.
+fn verify_signatures<R: Record>(
+    signed_records: &[SignedRecord<R>],
+    payloads: &[Vec<u8>],
+    first_index: usize,
+) -> Result<(), CustomErrs> {
+    if batch_is_valid(signed_records, payloads) {
+        return Ok(());
+    }
+
+    for (index, (signed_record, payload)) in signed_records.iter().zip(payloads).enumerate() {
+        signed_record
+            .verify_payload(payload)
+            .map_err(|err| err.in_record(first_index + index))?;
+    }
+    Ok(())
+}
+
+/// `true` if every record of `signed_records` carries enough valid signatures over its
+/// payload in `payloads`.
+///
+/// Batch and single verification are both cofactored, so this is `true` exactly when
+/// checking the records one by one succeeds, see `gen::verify_batch()`.
+fn batch_is_valid<R: Record>(signed_records: &[SignedRecord<R>], payloads: &[Vec<u8>]) -> bool {
+    let mut public_keys = vec![];
+    let mut messages: Vec<&[u8]> = vec![];
+    let mut signatures = vec![];
+
+    for (signed_record, payload) in signed_records.iter().zip(payloads) {
+        if signed_record
+            .signer
+            .check(&signed_record.signatures)
+            .is_err()
+        {
+            return false;
+        }
+        for (index, signature) in &signed_record.signatures {
+            public_keys.push(*signed_record.signer.key_at(*index).unwrap());
+            messages.push(payload);
+            signatures.push(*signature);
+        }
+    }
+    gen::verify_batch(&public_keys, &messages, &signatures).is_ok()
+}
+
+/// Outcome of `SignedRecord::verify_for_chain()` for each of `signed_records`.
+///
+/// The signatures are checked in one batch, and one by one only if the batch fails.
+fn record_verdicts<R: Record>(
+    signed_records: &[SignedRecord<R>],
+    chain_id: &Hash,
+) -> Vec<Result<(), CustomErrs>> {
+    let all_valid = signed_records
+        .iter()
+        .all(|signed_record| signed_record.chain_id == *chain_id)
+        && signed_records
+            .iter()
+            .map(SignedRecord::payload)
+            .collect::<Result<Vec<_>, _>>()
+            .is_ok_and(|payloads| batch_is_valid(signed_records, &payloads));
+
+    signed_records
+        .iter()
+        .map(|signed_record| match all_valid {
+            true => Ok(()),
+            false => signed_record.verify_for_chain(chain_id),
+        })
+        .collect()
+}
+
+/// Metadata linking a block to its predecessor.
+///
+/// The hash of a block is the hash of its header, so the header of block N
+/// commits to every block before it through `previous_hash`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Hash of the block this one was placed on top of
+    pub previous_hash: Hash,
+    /// Number of blocks below this one on the chain
+    pub height: u64,
+    /// Milliseconds since the unix epoch at which the header was built
+    pub timestamp: u64,
+    pub record_count: u64,
+    /// Merkle root of the records of the block, see `Block::records_root()`
+    pub records_root: Hash,
+    /// Work target the header was sealed for, see `consensus::ProofOfWork`
+    pub difficulty: u32,
+    /// Value searched by the miner so that the header's hash meets `difficulty`
+    pub nonce: u64,
+    /// Public key of the authority that sealed the header, see `consensus::ProofOfAuthority`
+    pub sealer: Option<PublicKey>,
+    /// Signature by `sealer` over `seal_message()`
+    pub seal: Option<Signature>,
+    /// Change of the authority set carried by this block, if any
+    pub governance: Option<GovernanceRecord>,
+}
+
+impl BlockHeader {
+    /// The `previous_hash` of the first block on a chain hashed with `algorithm`
+    pub fn null_hash(algorithm: HashAlgorithm) -> Hash {
+        Hash::new(algorithm, [0; HASH_LEN])
+    }
+
+    /// Hash of the block, with the algorithm of the chain's `Genesis`
+    pub fn hash(&self, algorithm: HashAlgorithm) -> Hash {
+        // Unlike a `Record`, a header only holds plain values, which bincode always encodes
+        algorithm
+            .encrypt(self)
+            .expect("a block header always serializes")
+    }
+
+    /// Bytes signed by the sealer: the whole header except the seal itself
+    pub fn seal_message(&self) -> Result<Vec<u8>, CustomErrs> {
+        let unsealed = BlockHeader {
+            seal: None,
+            ..self.clone()
+        };
+        bincode::serialize(&unsealed)
+            .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })
+    }
+}
+
 pub struct VerifiedBlock<R: Record> {
-    pub hash: Vec<u8>,
+    pub hash: Hash,
+    pub header: BlockHeader,
     pub block: Block<R>,
 }
 
 impl<R: Record> VerifiedBlock<R> {
-    pub fn get_hash(&self) -> &[u8] {
+    pub fn get_hash(&self) -> &Hash {
         &self.hash
     }
 
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
     pub fn get_block(&self) -> &Block<R> {
         &self.block
     }
@@ -206,7 +603,10 @@ impl<R: Record> VerifiedBlock<R> {
 #[derive(Debug)]
 pub struct FeedBack<R: Record> {
     pub block_position: QueryRange,
-    pub hash: Vec<u8>,
+    pub hash: Hash,
+    /// Algorithm of the chain the block was placed on
+    pub hash_algorithm: HashAlgorithm,
+    pub header: BlockHeader,
     pub block: Block<R>,
 }
 
@@ -214,28 +614,87 @@ impl<R: Record> FeedBack<R> {
     pub fn get_block(&self) -> &Block<R> {
         &self.block
     }
-}
 
-///
-///
-///
-///
-///
-///
-///
-///
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Receipt proving that the record at `index` was published in this block.
+    ///
+    /// The proof verifies against `header.records_root`.
+    pub fn proof(&self, index: usize) -> Result<Option<MerkleProof>, CustomErrs> {
+        self.block.proof(self.hash_algorithm, index)
+    }
+
+    /// One receipt per record of the block, in order
+    pub fn proofs(&self) -> Result<Vec<MerkleProof>, CustomErrs> {
+        let tree = self.block.merkle_tree(self.hash_algorithm)?;
+        Ok((0..tree.leaf_count())
+            .filter_map(|index| tree.proof(index))
+            .collect())
+    }
+}
 
+#[derive(Debug, Clone)]
 pub struct PublishedBlock {
-    hash: Vec<u8>,
+    hash: Hash,
+    header: BlockHeader,
     block_position: QueryRange,
 }
 
 impl PublishedBlock {
-    pub fn to_vec(&self) -> Vec<String> {
-        vec![
-            format!("{:?}", self.hash),
-            serde_json::to_string(&self.block_position).unwrap(),
-        ]
+    pub fn to_vec(&self) -> Result<Vec<String>, CustomErrs> {
+        Ok(vec![
+            self.hash.to_string(),
+            self.header.previous_hash.to_string(),
+            self.header.height.to_string(),
+            self.header.timestamp.to_string(),
+            self.header.record_count.to_string(),
+            self.header.records_root.to_string(),
+            self.header.difficulty.to_string(),
+            self.header.nonce.to_string(),
+            to_column(&self.header.sealer)?,
+            to_column(&self.header.seal)?,
+            to_column(&self.header.governance)?,
+            to_column(&self.block_position)?,
+        ])
+    }
+
+    /// Rebuilds a `PublishedBlock` from a row produced by `to_vec()`
+    pub fn from_vec(row: &[String]) -> Result<Self, CustomErrs> {
+        match row {
+            [hash, previous_hash, height, timestamp, record_count, records_root, difficulty, nonce, sealer, seal, governance, block_position] => {
+                Ok(Self {
+                    hash: hash_column(BLOCKS, hash)?,
+                    header: BlockHeader {
+                        previous_hash: hash_column(BLOCKS, previous_hash)?,
+                        height: from_column(BLOCKS, height)?,
+                        timestamp: from_column(BLOCKS, timestamp)?,
+                        record_count: from_column(BLOCKS, record_count)?,
+                        records_root: hash_column(BLOCKS, records_root)?,
+                        difficulty: from_column(BLOCKS, difficulty)?,
+                        nonce: from_column(BLOCKS, nonce)?,
+                        sealer: from_column(BLOCKS, sealer)?,
+                        seal: from_column(BLOCKS, seal)?,
+                        governance: from_column(BLOCKS, governance)?,
+                    },
+                    block_position: from_column(BLOCKS, block_position)?,
+                })
+            }
+            _ => Err(column_count_mismatch(BLOCKS, &BLOCKS_COLUMNS, row)),
+        }
+    }
+
+    pub fn get_hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn get_block_position(&self) -> QueryRange {
+        self.block_position
     }
 }
 
@@ -245,12 +704,12 @@ pub struct ItemsIter<'a> {
 }
 
 impl<'a> Iterator for ItemsIter<'a> {
-    type Item = Vec<String>;
+    type Item = Result<Vec<String>, CustomErrs>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.picked {
             false => {
-                self.picked = false;
+                self.picked = true;
                 Some(self.items.to_vec())
             }
             true => None,
@@ -259,73 +718,412 @@ impl<'a> Iterator for ItemsIter<'a> {
 }
 
 impl<'a> IntoIterator for &'a PublishedBlock {
-    type Item = Vec<String>;
+    type Item = Result<Vec<String>, CustomErrs>;
 
     type IntoIter = ItemsIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
         ItemsIter {
             picked: false,
-            items: &self,
+            items: self,
         }
     }
 }
 
 impl DatabaseInsertable for &PublishedBlock {
     fn get_name() -> &'static str {
-        &BLOCKS
+        BLOCKS
     }
 
     fn columns() -> &'static [&'static str] {
         &BLOCKS_COLUMNS
     }
 
+    fn key_column() -> Option<&'static str> {
+        Some(BLOCKS_COLUMNS[0])
+    }
+
     fn len(&self) -> i64 {
         1
     }
 }
 
-pub struct BlockChain<D: Database2> {
+pub struct BlockChain<D: Database2, C: Consensus = NoConsensus> {
     database: D,
+    genesis: Genesis,
+    consensus: C,
+    nonces: NonceTracker,
+    validation_pool: Option<ValidationPool>,
+    max_clock_drift: u64,
 }
 
 impl<D: Database2> BlockChain<D> {
-    pub fn open(database: D) -> Self {
-        Self { database }
+    /// Opens the chain stored in `database` without any consensus rules.
+    ///
+    /// See `open_with_consensus()`.
+    pub fn open(database: D, genesis: Genesis) -> Result<Self, CustomErrs> {
+        Self::open_with_consensus(database, genesis, NoConsensus)
+    }
+}
+
+impl<D: Database2, C: Consensus> BlockChain<D, C> {
+    /// Opens the chain stored in `database`, sealing and validating blocks with `consensus`.
+    ///
+    /// An empty database is initialized with `genesis` as its first block. Otherwise the
+    /// stored genesis block must be the one described by `genesis`, or
+    /// `CustomErrs::GenesisMismatch` is returned.
+    pub fn open_with_consensus(
+        mut database: D,
+        genesis: Genesis,
+        consensus: C,
+    ) -> Result<Self, CustomErrs> {
+        match database.size_of_table::<&PublishedBlock>()? {
+            Some(len) if len > 0 => {
+                let stored = PublishedBlock::from_vec(&database.get_row::<&PublishedBlock>(0)?)?;
+                if stored.hash != genesis.hash() {
+                    return Err(CustomErrs::GenesisMismatch {
+                        expected: genesis.hash(),
+                        found: stored.hash,
+                    });
+                }
+            }
+            _ => {
+                let published_block = PublishedBlock {
+                    hash: genesis.hash(),
+                    header: genesis.header(),
+                    block_position: QueryRange::new(0, -1),
+                };
+                database.transaction(|database| {
+                    database.insert(&&genesis)?;
+                    database.insert(&&published_block)
+                })?;
+            }
+        }
+
+        let nonces = NonceTracker::load(&database)?;
+
+        Ok(Self {
+            database,
+            genesis,
+            consensus,
+            nonces,
+            validation_pool: None,
+            max_clock_drift: DEFAULT_MAX_CLOCK_DRIFT,
+        })
+    }
+
+    /// Validates blocks on `pool` rather than on the calling thread, see `ValidationPool`
+    pub fn with_validation_pool(mut self, pool: ValidationPool) -> Self {
+        self.validation_pool = Some(pool);
+        self
+    }
+
+    pub fn validation_pool(&self) -> Option<&ValidationPool> {
+        self.validation_pool.as_ref()
+    }
+
+    /// Rejects headers whose timestamp is more than `max_clock_drift` milliseconds ahead
+    /// of the local clock, so that a sealer cannot pull the chain's time, and with it the
+    /// retargeted difficulty, into the future
+    pub fn with_max_clock_drift(mut self, max_clock_drift: u64) -> Self {
+        self.max_clock_drift = max_clock_drift;
+        self
+    }
+
+    pub fn max_clock_drift(&self) -> u64 {
+        self.max_clock_drift
+    }
+
+    pub fn genesis(&self) -> &Genesis {
+        &self.genesis
+    }
+
+    pub fn consensus(&self) -> &C {
+        &self.consensus
+    }
+
+    /// Identifier of this chain, derived from its genesis block
+    pub fn chain_id(&self) -> Hash {
+        self.genesis.chain_id()
+    }
+
+    /// Algorithm blocks of this chain are hashed with, set by its `Genesis`
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.genesis.hash_algorithm
+    }
+
+    /// Nonce the next record signed by `signer` must carry to be accepted
+    pub fn next_nonce(&self, signer: &Signer) -> u64 {
+        self.nonces.next(signer)
+    }
+
+    /// Returns the most recently published block, or `None` if the chain is empty
+    pub fn tip(&self) -> Result<Option<PublishedBlock>, CustomErrs> {
+        match self.database.size_of_table::<&PublishedBlock>()? {
+            Some(len) if len > 0 => self.get_published_block_at(len - 1).map(Some),
+            _ => Ok(None),
+        }
     }
 
-    fn append<R: Record>(&mut self, block: &Block<R>) -> Result<QueryRange, CustomErrs> {
-        self.database.insert(&block)
+    /// Builds the header `block` would carry if it were pushed on top of the current tip.
+    ///
+    /// The consensus fields are left unset, see `seal()`.
+    pub fn next_header<R: Record>(&self, block: &Block<R>) -> Result<BlockHeader, CustomErrs> {
+        block.header(self.tip()?.as_ref(), self.hash_algorithm())
     }
 
-    fn record(&mut self, published_block: &PublishedBlock) -> Result<QueryRange, CustomErrs> {
-        self.database.insert(&published_block)
+    /// Builds the header for `block` on top of the current tip and seals it with the
+    /// consensus engine, e.g. by searching a proof-of-work nonce
+    pub fn seal<R: Record>(&self, block: &Block<R>) -> Result<BlockHeader, CustomErrs> {
+        self.seal_header(self.next_header(block)?)
     }
 
+    /// Seals a header built with `next_header()`, after the caller adjusted it,
+    /// e.g. by attaching a `GovernanceRecord`
+    pub fn seal_header(&self, mut header: BlockHeader) -> Result<BlockHeader, CustomErrs> {
+        let tip = self.tip()?.ok_or(CustomErrs::EmptyChain)?;
+        self.consensus.seal(&mut header, &tip.header, self)?;
+        Ok(header)
+    }
+
+    /// Seals `block` on top of the current tip and places it on the chain
     pub fn push<R: Record>(&mut self, block: &Block<R>) -> Result<FeedBack<R>, CustomErrs> {
+        let header = self.seal(block)?;
+        self.push_with_header(header, block)
+    }
+
+    /// Places `block` on the chain under the given, already sealed, header.
+    ///
+    /// Fails if the header does not extend the current tip, does not describe `block`
+    /// or is rejected by the consensus engine.
+    pub fn push_with_header<R: Record>(
+        &mut self,
+        header: BlockHeader,
+        block: &Block<R>,
+    ) -> Result<FeedBack<R>, CustomErrs> {
+        self.check_next(&header, block)?;
+        let verified_block = match &self.validation_pool {
+            Some(pool) => pool.verify_block(block, &header, self.hash_algorithm())?,
+            None => block.verify(&header, self.hash_algorithm())?,
+        };
+        self.append(verified_block)
+    }
+
+    /// Places `blocks` on the chain in order, as many calls to `push_with_header()` would.
+    ///
+    /// With a validation pool, the blocks are all verified concurrently beforehand. They are
+    /// still placed on the chain one after the other: when `CustomErrs::InvalidBlock` is
+    /// returned, the blocks before the rejected one are on the chain and the others are not.
+    pub fn import<R: Record>(
+        &mut self,
+        blocks: &[(BlockHeader, Block<R>)],
+    ) -> Result<Vec<FeedBack<R>>, CustomErrs> {
+        let verdicts = match &self.validation_pool {
+            Some(pool) => pool.verify_blocks(blocks, self.hash_algorithm()),
+            None => blocks
+                .iter()
+                .map(|(header, block)| block.verify(header, self.hash_algorithm()))
+                .collect(),
+        };
+
+        let mut feedbacks = Vec::with_capacity(blocks.len());
+        for (index, ((header, block), verdict)) in blocks.iter().zip(verdicts).enumerate() {
+            let feedback = self
+                .check_next(header, block)
+                .and(verdict)
+                .and_then(|verified_block| self.append(verified_block))
+                .map_err(|err| err.in_block(index))?;
+            feedbacks.push(feedback);
+        }
+        Ok(feedbacks)
+    }
+
+    /// Checks that `header` can be placed on top of the current tip
+    fn check_next<R: Record>(
+        &self,
+        header: &BlockHeader,
+        block: &Block<R>,
+    ) -> Result<(), CustomErrs> {
         if block.size() == 0 {
             return Err(CustomErrs::EmptyBlocksNotAllowed);
         }
 
-        let VerifiedBlock { hash, block } = block.verify()?;
-        let block_position = self.append(&block)?;
-        let published_block = PublishedBlock {
-            hash: hash.clone(),
-            block_position,
-        };
-        self.record(&published_block)?;
+        let tip = self.tip()?.ok_or(CustomErrs::EmptyChain)?;
+
+        if header.previous_hash != tip.hash {
+            return Err(CustomErrs::PreviousHashMismatch {
+                expected: tip.hash,
+                found: header.previous_hash,
+            });
+        }
+
+        if header.height != tip.header.height + 1 {
+            return Err(CustomErrs::InvalidBlockHeight {
+                expected: tip.header.height + 1,
+                found: header.height,
+            });
+        }
+
+        if header.timestamp < tip.header.timestamp {
+            return Err(CustomErrs::TimestampBeforeParent);
+        }
+
+        let limit = now_millis().saturating_add(self.max_clock_drift);
+        if header.timestamp > limit {
+            return Err(CustomErrs::TimestampInFuture {
+                timestamp: header.timestamp,
+                limit,
+            });
+        }
+
+        self.consensus.validate(header, &tip.header, self)
+    }
+
+    /// Writes a block accepted by `check_next()` and verified against its header
+    fn append<R: Record>(
+        &mut self,
+        verified_block: VerifiedBlock<R>,
+    ) -> Result<FeedBack<R>, CustomErrs> {
+        let VerifiedBlock {
+            hash,
+            header,
+            block,
+        } = verified_block;
+        let chain_id = self.chain_id();
+        for (index, signed_record) in block.get_signed_records().iter().enumerate() {
+            if signed_record.chain_id != chain_id {
+                return Err(CustomErrs::WrongChain {
+                    expected: chain_id,
+                    found: signed_record.chain_id,
+                }
+                .in_record(index));
+            }
+        }
+        let nonces = self.nonces.check(&block)?;
+        let signed_records = block.get_signed_records();
+        for (index, signed_record) in signed_records.iter().enumerate() {
+            signed_record
+                .record
+                .validate_against(&signed_record.signer, self, &signed_records[..index])
+                .map_err(|err| err.in_record(index))?;
+        }
+
+        // The records and the entry pointing at them are written together or not at all
+        let block_position = self.database.transaction(|database| {
+            let block_position = database.insert(&&block)?;
+            let published_block = PublishedBlock {
+                hash,
+                header: header.clone(),
+                block_position,
+            };
+            database.insert(&&published_block)?;
+            Ok(block_position)
+        })?;
+        self.nonces.apply(nonces);
         Ok(FeedBack {
             hash,
-            block: block.clone(),
+            hash_algorithm: self.hash_algorithm(),
+            header,
+            block,
             block_position,
         })
     }
 
-    pub fn get_records<R: Record>(&self, _block_position: QueryRange) -> Block<R> {
-        unimplemented!()
+    /// Reads back the records stored at `block_position`, as reported by `FeedBack`
+    pub fn get_records<R: Record>(
+        &self,
+        block_position: QueryRange,
+    ) -> Result<Block<R>, CustomErrs> {
+        let rows = self.database.get_rows::<&Block<R>>(block_position)?;
+
+        if rows.len() as i64 != block_position.len() {
+            return Err(CustomErrs::NoSuchRowInTable {
+                table: RECORDS.to_owned(),
+                position: block_position.begin + rows.len() as i64,
+            });
+        }
+
+        let signed_records = rows
+            .iter()
+            .map(|row| SignedRecord::from_vec(row))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Block { signed_records })
+    }
+
+    /// Looks up the published block with the given hash and reads back its records
+    pub fn get_block<R: Record>(&self, hash: &Hash) -> Result<Block<R>, CustomErrs> {
+        let published_block = self.get_published_block(hash)?;
+        self.get_records(published_block.block_position)
+    }
+
+    /// Returns the `BLOCKCHAIN` entry at `height`
+    pub fn get_published_block_at(&self, height: i64) -> Result<PublishedBlock, CustomErrs> {
+        let row = self
+            .database
+            .get_row::<&PublishedBlock>(height)
+            .map_err(|err| match err {
+                CustomErrs::NoSuchRowInTable { .. } => CustomErrs::NoBlockAtHeight { height },
+                err => err,
+            })?;
+        PublishedBlock::from_vec(&row)
+    }
+
+    /// Looks up the `BLOCKCHAIN` entry with the given hash
+    pub fn get_published_block(&self, hash: &Hash) -> Result<PublishedBlock, CustomErrs> {
+        let rows = self
+            .database
+            .find_rows::<&PublishedBlock>(BLOCKS_COLUMNS[0], &hash.to_string())?;
+
+        match rows.first() {
+            Some(row) => PublishedBlock::from_vec(row),
+            None => Err(CustomErrs::NoSuchBlockInDatabase { hash: *hash }),
+        }
+    }
+}
+
+fn to_column<V: Serialize>(value: &V) -> Result<String, CustomErrs> {
+    serde_json::to_string(value).map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })
+}
+
+fn from_column<V: DeserializeOwned>(table: &str, column: &str) -> Result<V, CustomErrs> {
+    serde_json::from_str(column).map_err(|err| CustomErrs::CorruptedDatabaseRow {
+        table: table.to_owned(),
+        source: err.into(),
+    })
+}
+
+/// Parses a column holding a `Hash` in its hex form
+fn hash_column(table: &str, column: &str) -> Result<Hash, CustomErrs> {
+    column
+        .parse()
+        .map_err(|err: CustomErrs| CustomErrs::CorruptedDatabaseRow {
+            table: table.to_owned(),
+            source: err.into(),
+        })
+}
+
+fn column_count_mismatch(table: &str, columns: &[&str], row: &[String]) -> CustomErrs {
+    CustomErrs::ColumnCountMismatch {
+        table: table.to_owned(),
+        expected: columns.len(),
+        found: row.len(),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl<D: Database2, C: Consensus> ChainView for BlockChain<D, C> {
+    fn header_at(&self, height: u64) -> Result<BlockHeader, CustomErrs> {
+        Ok(self.get_published_block_at(height as i64)?.header)
     }
 
-    pub fn get_block<R: Record>(&self, _hash: &Hash) -> Block<R> {
-        unimplemented!()
+    fn genesis(&self) -> &Genesis {
+        &self.genesis
     }
 }
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errs::CustomErrs,
+    gen::{Hash, PrivateKey, PublicKey, Signature},
+};
+
+use super::{Record, SignedRecord, SIGNING_VERSION};
+
+/// Largest number of keys in a `SignerSet`, so that each key has a `u8` index
+pub const MAX_SIGNERS: usize = u8::MAX as usize;
+
+/// Key, or set of keys, authorizing a `SignedRecord`.
+///
+/// Nonces are counted per signer: a `SignerSet` has its own sequence of nonces, separate
+/// from those of its keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Signer {
+    Key(PublicKey),
+    Set(SignerSet),
+}
+
+/// `threshold` out of `signers`, e.g. the approvers of a shared treasury.
+///
+/// Each key is known by its index in `signers()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignerSet {
+    signers: Vec<PublicKey>,
+    threshold: u8,
+}
+
+impl Signer {
+    /// Key expected to have made the signature at `index`
+    pub fn key_at(&self, index: u8) -> Option<&PublicKey> {
+        match self {
+            Self::Key(public_key) => (index == 0).then_some(public_key),
+            Self::Set(signer_set) => signer_set.signers.get(index as usize),
+        }
+    }
+
+    /// Number of valid signatures a record of this signer needs
+    pub fn threshold(&self) -> u8 {
+        match self {
+            Self::Key(_) => 1,
+            Self::Set(signer_set) => signer_set.threshold,
+        }
+    }
+
+    /// Checks that `signatures` name keys of this signer in increasing order and are
+    /// enough to reach the threshold, without checking the signatures themselves
+    pub fn check(&self, signatures: &[(u8, Signature)]) -> Result<(), CustomErrs> {
+        if let Self::Set(signer_set) = self {
+            SignerSet::check(&signer_set.signers, signer_set.threshold)?;
+        }
+
+        let mut previous = None;
+        for (index, _) in signatures {
+            if self.key_at(*index).is_none() || previous >= Some(*index) {
+                return Err(CustomErrs::InvalidSignerIndex { index: *index });
+            }
+            previous = Some(*index);
+        }
+
+        if signatures.len() < self.threshold() as usize {
+            return Err(CustomErrs::NotEnoughSignatures {
+                threshold: self.threshold(),
+                found: signatures.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl From<PublicKey> for Signer {
+    fn from(public_key: PublicKey) -> Self {
+        Self::Key(public_key)
+    }
+}
+
+impl From<SignerSet> for Signer {
+    fn from(signer_set: SignerSet) -> Self {
+        Self::Set(signer_set)
+    }
+}
+
+impl SignerSet {
+    pub fn new(signers: Vec<PublicKey>, threshold: u8) -> Result<Self, CustomErrs> {
+        Self::check(&signers, threshold)?;
+        Ok(Self { signers, threshold })
+    }
+
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Index of `public_key` in the set, if it is part of it
+    pub fn index_of(&self, public_key: &PublicKey) -> Option<u8> {
+        self.signers
+            .iter()
+            .position(|signer| signer == public_key)
+            .map(|index| index as u8)
+    }
+
+    fn check(signers: &[PublicKey], threshold: u8) -> Result<(), CustomErrs> {
+        if signers.len() > MAX_SIGNERS || threshold == 0 || threshold as usize > signers.len() {
+            return Err(CustomErrs::InvalidSignerSet {
+                signers: signers.len(),
+                threshold,
+            });
+        }
+
+        for (index, public_key) in signers.iter().enumerate() {
+            if signers[..index].contains(public_key) {
+                return Err(CustomErrs::DuplicateSigner {
+                    public_key: *public_key,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record collecting the signatures of a `SignerSet` before it can be published.
+///
+/// The proposal is passed from one holder of a key of the set to the next, e.g. with
+/// `codec::Codec`, each adding a signature with `sign()` or `Entity::co_sign()`. Once
+/// `is_complete()`, `finish()` turns it into a `SignedRecord`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Record")]
+pub struct MultiSigProposal<T: Record> {
+    signer_set: SignerSet,
+    /// Signatures gathered so far, ordered by key index
+    signatures: Vec<(u8, Signature)>,
+    chain_id: Hash,
+    nonce: u64,
+    record: T,
+}
+
+impl<T: Record> MultiSigProposal<T> {
+    /// Proposes `record` as the `nonce`-th record of `signer_set` on the chain `chain_id`,
+    /// see `BlockChain::next_nonce()`
+    pub fn new(record: T, signer_set: SignerSet, chain_id: &Hash, nonce: u64) -> Self {
+        Self {
+            signer_set,
+            signatures: vec![],
+            chain_id: *chain_id,
+            nonce,
+            record,
+        }
+    }
+
+    /// Bytes every key of the set signs, see `SignedRecord::signing_payload()`
+    pub fn payload(&self) -> Result<Vec<u8>, CustomErrs> {
+        SignedRecord::signing_payload(
+            SIGNING_VERSION,
+            &Signer::Set(self.signer_set.clone()),
+            &self.chain_id,
+            self.nonce,
+            &self.record,
+        )
+    }
+
+    /// Adds the signature of `private_key`, whose public key must be part of the set
+    pub fn sign(&mut self, private_key: &PrivateKey) -> Result<(), CustomErrs> {
+        let public_key = private_key.public_key();
+        let index = self
+            .signer_set
+            .index_of(&public_key)
+            .ok_or(CustomErrs::NotASigner { public_key })?;
+        let signature = private_key.sign(&self.payload()?);
+        self.add_signature(index, signature)
+    }
+
+    /// Adds a signature over `payload()` made elsewhere by the key at `index`, after
+    /// checking it
+    pub fn add_signature(&mut self, index: u8, signature: Signature) -> Result<(), CustomErrs> {
+        let public_key = self
+            .signer_set
+            .signers
+            .get(index as usize)
+            .ok_or(CustomErrs::InvalidSignerIndex { index })?;
+        public_key.verify(&self.payload()?, &signature)?;
+
+        match self
+            .signatures
+            .binary_search_by_key(&index, |(index, _)| *index)
+        {
+            Ok(_) => Err(CustomErrs::AlreadySigned { index }),
+            Err(position) => {
+                self.signatures.insert(position, (index, signature));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn signer_set(&self) -> &SignerSet {
+        &self.signer_set
+    }
+
+    pub fn signatures(&self) -> &[(u8, Signature)] {
+        &self.signatures
+    }
+
+    pub fn get_record(&self) -> &T {
+        &self.record
+    }
+
+    /// `true` once enough keys signed to reach the threshold
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() >= self.signer_set.threshold as usize
+    }
+
+    /// The signed record, if enough keys signed
+    pub fn finish(self) -> Result<SignedRecord<T>, CustomErrs> {
+        if !self.is_complete() {
+            return Err(CustomErrs::NotEnoughSignatures {
+                threshold: self.signer_set.threshold,
+                found: self.signatures.len(),
+            });
+        }
+
+        Ok(SignedRecord {
+            signer: Signer::Set(self.signer_set),
+            signatures: self.signatures,
+            version: SIGNING_VERSION,
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            record: self.record,
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::{
+    errs::CustomErrs,
+    io::{Database2, DatabaseInsertable, QueryRange},
+};
+
+use super::{column_count_mismatch, from_column, Block, Record, Signer, RECORDS, RECORDS_COLUMNS};
+
+/// Number of record rows read from the database at once by `NonceTracker::load()`
+const LOAD_CHUNK_LEN: i64 = 1024;
+
+/// Stands for the records table when no record type is at hand
+struct RecordRows;
+
+impl DatabaseInsertable for RecordRows {
+    fn get_name() -> &'static str {
+        RECORDS
+    }
+
+    fn columns() -> &'static [&'static str] {
+        &RECORDS_COLUMNS
+    }
+
+    fn len(&self) -> i64 {
+        0
+    }
+}
+
+/// Last nonce accepted from every signer on the chain
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NonceTracker {
+    last: HashMap<Signer, u64>,
+}
+
+impl NonceTracker {
+    /// Rebuilds the tracker from every record stored in `database`, reading
+    /// `LOAD_CHUNK_LEN` rows at a time
+    pub(crate) fn load<D: Database2>(database: &D) -> Result<Self, CustomErrs> {
+        let mut tracker = Self::default();
+        let len = database.size_of_table::<RecordRows>()?.unwrap_or(0);
+
+        for begin in (0..len).step_by(LOAD_CHUNK_LEN as usize) {
+            let end = (begin + LOAD_CHUNK_LEN).min(len) - 1;
+            for row in database.get_rows::<RecordRows>(QueryRange::new(begin, end))? {
+                match row.as_slice() {
+                    [_, signer, _, nonce, _, _] => {
+                        tracker
+                            .last
+                            .insert(from_column(RECORDS, signer)?, from_column(RECORDS, nonce)?);
+                    }
+                    row => return Err(column_count_mismatch(RECORDS, &RECORDS_COLUMNS, row)),
+                }
+            }
+        }
+
+        Ok(tracker)
+    }
+
+    /// Nonce the next record of `signer` must carry
+    pub(crate) fn next(&self, signer: &Signer) -> u64 {
+        self.last.get(signer).map_or(0, |last| last + 1)
+    }
+
+    /// Checks that `nonce` is the next one for `signer` and records it
+    pub(crate) fn accept(&mut self, signer: &Signer, nonce: u64) -> Result<(), CustomErrs> {
+        Self::expect(self.next(signer), nonce)?;
+        self.last.insert(signer.clone(), nonce);
+        Ok(())
+    }
+
+    /// Checks the nonces of every record of `block`, in order, without recording them.
+    ///
+    /// Returns the updates to hand to `apply()` once the block is stored.
+    pub(crate) fn check<R: Record>(
+        &self,
+        block: &Block<R>,
+    ) -> Result<HashMap<Signer, u64>, CustomErrs> {
+        let mut staged: HashMap<Signer, u64> = HashMap::new();
+
+        for (index, signed_record) in block.get_signed_records().iter().enumerate() {
+            let signer = signed_record.get_signer();
+            let next = match staged.get(signer) {
+                Some(last) => last + 1,
+                None => self.next(signer),
+            };
+            Self::expect(next, signed_record.nonce).map_err(|err| err.in_record(index))?;
+            staged.insert(signer.clone(), signed_record.nonce);
+        }
+
+        Ok(staged)
+    }
+
+    pub(crate) fn apply(&mut self, updates: HashMap<Signer, u64>) {
+        self.last.extend(updates);
+    }
+
+    fn expect(next: u64, nonce: u64) -> Result<(), CustomErrs> {
+        if nonce < next {
+            Err(CustomErrs::ReplayedNonce {
+                expected: next,
+                found: nonce,
+            })
+        } else if nonce > next {
+            Err(CustomErrs::NonceGap {
+                expected: next,
+                found: nonce,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use rayon::prelude::*;
+
+use crate::{
+    errs::CustomErrs,
+    gen::{merkle::MerkleTree, Hash, HashAlgorithm},
+};
+
+use super::{
+    record_verdicts, verify_signatures, Block, BlockHeader, Record, SignedRecord, VerifiedBlock,
+};
+
+/// Thread pool spreading the validation of blocks over several workers.
+///
+/// Its verdicts, including the index of the first invalid record, are always the ones the
+/// sequential `Block::verify()` gives. Attach it to a chain with
+/// `BlockChain::with_validation_pool()`.
+pub struct ValidationPool {
+    pool: rayon::ThreadPool,
+}
+
+impl ValidationPool {
+    /// Number of records whose signatures a worker checks in one batch
+    pub const CHUNK_LEN: usize = 256;
+
+    /// Starts a pool of `threads` workers, or of one worker per CPU if `threads` is 0
+    pub fn new(threads: usize) -> Result<Self, CustomErrs> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("validation-{}", index))
+            .build()
+            .map_err(|err| CustomErrs::CouldNotStartThreadPool { source: err.into() })?;
+        Ok(Self { pool })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Same as `Block::verify()`, with the records checked and hashed concurrently
+    pub fn verify_block<R: Record>(
+        &self,
+        block: &Block<R>,
+        header: &BlockHeader,
+        algorithm: HashAlgorithm,
+    ) -> Result<VerifiedBlock<R>, CustomErrs> {
+        self.pool
+            .install(|| verify_block_parallel(block, header, algorithm))
+    }
+
+    /// Verifies independent blocks concurrently, each one as `verify_block()` does.
+    ///
+    /// The verdicts are returned in the order of `blocks`.
+    pub fn verify_blocks<R: Record>(
+        &self,
+        blocks: &[(BlockHeader, Block<R>)],
+        algorithm: HashAlgorithm,
+    ) -> Vec<Result<VerifiedBlock<R>, CustomErrs>> {
+        self.pool.install(|| {
+            blocks
+                .par_iter()
+                .map(|(header, block)| verify_block_parallel(block, header, algorithm))
+                .collect()
+        })
+    }
+
+    /// Same as `Block::records_root()`, with the tree hashed concurrently
+    pub fn records_root<R: Record>(
+        &self,
+        block: &Block<R>,
+        algorithm: HashAlgorithm,
+    ) -> Result<Hash, CustomErrs> {
+        self.pool.install(|| {
+            Ok(MerkleTree::from_items_parallel(algorithm, &block.signed_records)?.root())
+        })
+    }
+
+    /// Outcome of `SignedRecord::verify_for_chain()` for each of `signed_records`, in order
+    pub(crate) fn record_verdicts<R: Record>(
+        &self,
+        signed_records: &[SignedRecord<R>],
+        chain_id: &Hash,
+    ) -> Vec<Result<(), CustomErrs>> {
+        self.pool.install(|| {
+            signed_records
+                .par_chunks(ValidationPool::CHUNK_LEN)
+                .flat_map_iter(|chunk| record_verdicts(chunk, chain_id))
+                .collect()
+        })
+    }
+}
+
+/// `Block::verify()` on the current rayon pool
+fn verify_block_parallel<R: Record>(
+    block: &Block<R>,
+    header: &BlockHeader,
+    algorithm: HashAlgorithm,
+) -> Result<VerifiedBlock<R>, CustomErrs> {
+    let (signatures, records_root) = rayon::join(
+        || {
+            verify_signatures_parallel(&block.signed_records)
+                .and_then(|()| validate_records_parallel(&block.signed_records))
+        },
+        || MerkleTree::from_items_parallel(algorithm, &block.signed_records),
+    );
+    signatures?;
+    block.check_header(header, &records_root?.root())?;
+    Ok(block.verified(header, algorithm))
+}
+
+/// Checks the signatures of `signed_records` one chunk per worker.
+///
+/// Every chunk is checked, and the verdicts are then read in order, so the error reported
+/// is the one of the first invalid record, as in `Block::verify()`.
+fn verify_signatures_parallel<R: Record>(
+    signed_records: &[SignedRecord<R>],
+) -> Result<(), CustomErrs> {
+    let payloads = signed_records
+        .par_iter()
+        .enumerate()
+        .map(|(index, signed_record)| signed_record.payload().map_err(|err| err.in_record(index)))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    signed_records
+        .par_chunks(ValidationPool::CHUNK_LEN)
+        .zip(payloads.par_chunks(ValidationPool::CHUNK_LEN))
+        .enumerate()
+        .map(|(chunk, (signed_records, payloads))| {
+            verify_signatures(signed_records, payloads, chunk * ValidationPool::CHUNK_LEN)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// `Block::validate_records()` with the records spread over the workers
+fn validate_records_parallel<R: Record>(
+    signed_records: &[SignedRecord<R>],
+) -> Result<(), CustomErrs> {
+    signed_records
+        .par_iter()
+        .enumerate()
+        .map(|(index, signed_record)| {
+            signed_record
+                .record
+                .validate()
+                .map_err(|err| err.in_record(index))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect()
+}
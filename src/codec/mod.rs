@@ -0,0 +1,125 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    blockchain::{Block, BlockHeader, Genesis, MultiSigProposal, Record, SignedRecord},
+    errs::CustomErrs,
+};
+
+/// Version of the encodings produced by this module, written in front of every value
+pub const VERSION: u8 = 1;
+
+/// Values that can be sent over the network or saved to a file and read back.
+///
+/// The binary form is the version byte followed by the bincode encoding of the value.
+/// The JSON form is an object holding the version and the value. Decoding either form
+/// gives back an identical value: a decoded `SignedRecord` still verifies, and a
+/// decoded `Block` has the same `records_root()`, so it verifies against the
+/// `BlockHeader` it was published under. That header, whose `hash()` identifies the block
+/// on its chain, decodes to the same hash as well.
+///
+/// # Example
+/// ```
+/// use blockchain::{
+///     blockchain::{Genesis, Record, SignedRecord},
+///     codec::Codec,
+///     gen,
+///     utils::Transaction,
+/// };
+///
+/// let key_pair = gen::generate_key_pair();
+/// let chain_id = Genesis::new("blockchain").chain_id();
+/// let record = Transaction::new("A", "B", "2")
+///     .sign(key_pair.private_key(), &chain_id, 0)
+///     .unwrap();
+///
+/// let decoded = SignedRecord::<Transaction>::from_bytes(&record.to_bytes().unwrap()).unwrap();
+/// assert!(decoded.verify().is_ok());
+///
+/// let decoded = SignedRecord::<Transaction>::from_json(&record.to_json().unwrap()).unwrap();
+/// assert_eq!(decoded.hash().unwrap(), record.hash().unwrap());
+/// ```
+pub trait Codec: Serialize + DeserializeOwned {
+    fn to_bytes(&self) -> Result<Vec<u8>, CustomErrs> {
+        to_bytes(self)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, CustomErrs> {
+        from_bytes(bytes)
+    }
+
+    fn to_json(&self) -> Result<String, CustomErrs> {
+        to_json(self)
+    }
+
+    fn from_json(json: &str) -> Result<Self, CustomErrs> {
+        from_json(json)
+    }
+}
+
+impl<R: Record> Codec for SignedRecord<R> {}
+
+impl<R: Record> Codec for MultiSigProposal<R> {}
+
+impl<R: Record> Codec for Block<R> {}
+
+impl Codec for BlockHeader {}
+
+impl Codec for Genesis {}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u8,
+    value: &'a T,
+}
+
+/// An `Envelope` whose value is only decoded once its version is known
+#[derive(Deserialize)]
+struct RawEnvelope {
+    version: u8,
+    value: serde_json::Value,
+}
+
+/// Encodes `value` as the version byte followed by its bincode encoding
+pub fn to_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, CustomErrs> {
+    let mut bytes = vec![VERSION];
+    bincode::serialize_into(&mut bytes, value)
+        .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })?;
+    Ok(bytes)
+}
+
+/// Decodes a value encoded by `to_bytes()`
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CustomErrs> {
+    match bytes.split_first() {
+        Some((&VERSION, encoded)) => bincode::deserialize(encoded)
+            .map_err(|err| CustomErrs::CouldNotDeserialize { source: err.into() }),
+        Some((version, _)) => Err(CustomErrs::UnsupportedCodecVersion { found: *version }),
+        None => Err(CustomErrs::CouldNotDeserialize {
+            source: bincode::Error::from(bincode::ErrorKind::Custom("empty input".to_owned()))
+                .into(),
+        }),
+    }
+}
+
+/// Encodes `value` as a JSON object of the form `{"version": 1, "value": ...}`
+pub fn to_json<T: Serialize>(value: &T) -> Result<String, CustomErrs> {
+    serde_json::to_string(&Envelope {
+        version: VERSION,
+        value,
+    })
+    .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })
+}
+
+/// Decodes a value encoded by `to_json()`
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, CustomErrs> {
+    let deserialize_error =
+        |err: serde_json::Error| CustomErrs::CouldNotDeserialize { source: err.into() };
+
+    let envelope: RawEnvelope = serde_json::from_str(json).map_err(deserialize_error)?;
+    if envelope.version != VERSION {
+        return Err(CustomErrs::UnsupportedCodecVersion {
+            found: envelope.version,
+        });
+    }
+
+    serde_json::from_value(envelope.value).map_err(deserialize_error)
+}
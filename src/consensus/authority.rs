@@ -0,0 +1,208 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blockchain::BlockHeader,
+    errs::CustomErrs,
+    gen::{self, Hash, KeyPair, PrivateKey, PublicKey, Signature},
+};
+
+use super::{ChainView, Consensus};
+
+/// Hash of a block along with the authority set in effect after it
+type AuthoritySnapshot = (Hash, Vec<PublicKey>);
+
+/// Replacement of the authority set, carried in a block header.
+///
+/// It only takes effect if a strict majority of the authorities in place when the block
+/// is sealed approved it. Approvals sign the chain ID, the hash of the block the record's
+/// header is placed on, the current set and the new set, so they cannot be reused on
+/// another chain or at another place of the chain, even once the set reverts to `current`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GovernanceRecord {
+    /// Public keys allowed to seal blocks once this record is published
+    pub authorities: Vec<PublicKey>,
+    /// (public key, signature) of every current authority approving the change
+    pub approvals: Vec<(PublicKey, Signature)>,
+}
+
+impl GovernanceRecord {
+    pub fn new(authorities: Vec<PublicKey>) -> Self {
+        Self {
+            authorities,
+            approvals: vec![],
+        }
+    }
+
+    /// Bytes each approving authority signs for a record carried by the child of `parent`
+    pub fn message(&self, chain_id: &Hash, parent: &Hash, current: &[PublicKey]) -> Vec<u8> {
+        bincode::serialize(&("governance", chain_id, parent, current, &self.authorities)).unwrap()
+    }
+
+    /// Adds the approval of the authority owning `private_key`
+    pub fn approve(
+        &mut self,
+        chain_id: &Hash,
+        parent: &Hash,
+        current: &[PublicKey],
+        private_key: &PrivateKey,
+    ) {
+        let signature = gen::sign(&self.message(chain_id, parent, current), private_key);
+        self.approvals.push((private_key.public_key(), signature));
+    }
+
+    /// Checks that the new set is usable and that a strict majority of `current` approved it
+    /// for the child of `parent`
+    pub fn verify(
+        &self,
+        chain_id: &Hash,
+        parent: &Hash,
+        current: &[PublicKey],
+    ) -> Result<(), CustomErrs> {
+        let distinct: HashSet<&PublicKey> = self.authorities.iter().collect();
+        if self.authorities.is_empty() || distinct.len() != self.authorities.len() {
+            return Err(CustomErrs::InvalidGovernanceRecord);
+        }
+
+        let message = self.message(chain_id, parent, current);
+        let mut approvers = HashSet::new();
+
+        for (public_key, signature) in &self.approvals {
+            if !current.contains(public_key) || !approvers.insert(public_key) {
+                return Err(CustomErrs::InvalidGovernanceRecord);
+            }
+            gen::verify_signature(public_key, &message, signature)?;
+        }
+
+        if approvers.len() * 2 <= current.len() {
+            return Err(CustomErrs::InsufficientGovernanceApprovals);
+        }
+
+        Ok(())
+    }
+}
+
+/// Proof-of-authority: blocks are sealed by a fixed set of ed25519 keys taking turns.
+///
+/// The block at height `h` must be sealed by `authorities[h % authorities.len()]`,
+/// which signs the header with `gen::sign`. The initial set is `Genesis::authorities`;
+/// a header carrying a valid `GovernanceRecord` replaces it from the next block on.
+///
+/// An instance created with `new()` only validates. Nodes that seal blocks use
+/// `with_sealer()` to hold their key.
+pub struct ProofOfAuthority {
+    sealer: Option<KeyPair>,
+    /// Authority set in effect after the block with the given hash, to avoid rescanning
+    /// the chain for every header
+    cache: Mutex<Option<AuthoritySnapshot>>,
+}
+
+impl Default for ProofOfAuthority {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProofOfAuthority {
+    pub fn new() -> Self {
+        Self {
+            sealer: None,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Seals blocks with the given key pair when it is this authority's turn
+    pub fn with_sealer(mut self, key_pair: KeyPair) -> Self {
+        self.sealer = Some(key_pair);
+        self
+    }
+
+    /// Authority set in effect for the block placed on top of `header`
+    pub fn authorities_after(
+        &self,
+        header: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<Vec<PublicKey>, CustomErrs> {
+        let hash = header.hash(chain.genesis().hash_algorithm);
+        let cached = self.cache.lock().unwrap().clone();
+
+        let before = match cached {
+            Some((cached_hash, authorities)) if cached_hash == hash => return Ok(authorities),
+            Some((cached_hash, authorities)) if cached_hash == header.previous_hash => authorities,
+            _ => {
+                let mut authorities = chain.genesis().authorities.clone();
+                for height in 1..header.height {
+                    if let Some(governance) = chain.header_at(height)?.governance {
+                        authorities = governance.authorities;
+                    }
+                }
+                authorities
+            }
+        };
+
+        let authorities = match &header.governance {
+            Some(governance) => governance.authorities.clone(),
+            None => before,
+        };
+
+        *self.cache.lock().unwrap() = Some((hash, authorities.clone()));
+        Ok(authorities)
+    }
+
+    /// Authority expected to seal the block at `height`
+    pub fn sealer_at(height: u64, authorities: &[PublicKey]) -> Result<&PublicKey, CustomErrs> {
+        if authorities.is_empty() {
+            return Err(CustomErrs::WrongSealer);
+        }
+        Ok(&authorities[(height % authorities.len() as u64) as usize])
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn seal(
+        &self,
+        header: &mut BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        let sealer = self.sealer.as_ref().ok_or(CustomErrs::NoSealingKey)?;
+        let authorities = self.authorities_after(parent, chain)?;
+
+        if Self::sealer_at(header.height, &authorities)? != sealer.public_key() {
+            return Err(CustomErrs::WrongSealer);
+        }
+
+        header.sealer = Some(*sealer.public_key());
+        header.seal = Some(sealer.sign(&header.seal_message()?));
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        header: &BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        let authorities = self.authorities_after(parent, chain)?;
+
+        let sealer = header.sealer.as_ref().ok_or(CustomErrs::WrongSealer)?;
+        if Self::sealer_at(header.height, &authorities)? != sealer {
+            return Err(CustomErrs::WrongSealer);
+        }
+
+        let seal = header.seal.as_ref().ok_or(CustomErrs::InvalidSeal)?;
+        gen::verify_signature(sealer, &header.seal_message()?, seal)
+            .map_err(|_| CustomErrs::InvalidSeal)?;
+
+        if let Some(governance) = &header.governance {
+            governance.verify(
+                &chain.genesis().chain_id(),
+                &header.previous_hash,
+                &authorities,
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+mod authority;
+
+use crate::{
+    blockchain::{BlockHeader, Genesis},
+    errs::CustomErrs,
+    gen::{HashAlgorithm, HASH_LEN},
+};
+
+pub use authority::{GovernanceRecord, ProofOfAuthority};
+
+/// Read access to the chain a header is being sealed or validated against
+pub trait ChainView {
+    /// Returns the header of the published block at `height`
+    fn header_at(&self, height: u64) -> Result<BlockHeader, CustomErrs>;
+
+    fn genesis(&self) -> &Genesis;
+}
+
+/// Rules deciding which blocks may extend the chain.
+///
+/// `BlockChain::push` asks the engine to `seal` every header it builds and to `validate`
+/// every header it is given, and `BlockChain::verify_chain` re-validates stored headers.
+pub trait Consensus {
+    /// Fills in the consensus fields of `header` so that `validate` accepts it.
+    ///
+    /// `header` already extends `parent`, the current tip of `chain`.
+    fn seal(
+        &self,
+        header: &mut BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs>;
+
+    /// Checks the consensus fields of `header`, which extends `parent`
+    fn validate(
+        &self,
+        header: &BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs>;
+}
+
+/// Accepts every block. The consensus fields of sealed headers are left at zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoConsensus;
+
+impl Consensus for NoConsensus {
+    fn seal(
+        &self,
+        _: &mut BlockHeader,
+        _: &BlockHeader,
+        _: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        _: &BlockHeader,
+        _: &BlockHeader,
+        _: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        Ok(())
+    }
+}
+
+/// Highest proof-of-work difficulty, met only by a hash whose bits are all zero
+pub const MAX_DIFFICULTY: u32 = (HASH_LEN * 8) as u32;
+
+/// Default upper bound of a retargeted difficulty, still within reach of a single CPU
+pub const DEFAULT_MAX_DIFFICULTY: u32 = 32;
+
+/// Policy adapting the proof-of-work difficulty to the rate at which blocks are produced.
+///
+/// Heights are grouped in windows of `window` blocks, starting at height 1. The first
+/// block of every window after the first one gets a new difficulty, computed from the
+/// timestamps of the previous window: each time blocks came twice as fast as
+/// `target_interval`, one bit of difficulty is added, and each time they came twice as
+/// slow, one bit is removed. Only integer arithmetic on stored headers is involved, so
+/// every node computes the same value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retarget {
+    window: u64,
+    target_interval: u64,
+    max_factor: u64,
+    min_difficulty: u32,
+    max_difficulty: u32,
+}
+
+impl Retarget {
+    /// Windows of `window` blocks, at least 2, aiming at one block every
+    /// `target_interval` milliseconds
+    pub fn new(window: u64, target_interval: u64) -> Self {
+        Self {
+            window: window.max(2),
+            target_interval,
+            max_factor: 4,
+            min_difficulty: 0,
+            max_difficulty: DEFAULT_MAX_DIFFICULTY,
+        }
+    }
+
+    /// Largest ratio between the observed and desired block rate acted upon in one
+    /// adjustment, at least 1, e.g. `4` moves the difficulty by at most two bits
+    pub fn with_max_factor(mut self, max_factor: u64) -> Self {
+        self.max_factor = max_factor.max(1);
+        self
+    }
+
+    /// Keeps the difficulty between the given bounds, the upper one capped at
+    /// `MAX_DIFFICULTY`
+    pub fn with_bounds(mut self, min_difficulty: u32, max_difficulty: u32) -> Self {
+        self.max_difficulty = max_difficulty.min(MAX_DIFFICULTY);
+        self.min_difficulty = min_difficulty.min(self.max_difficulty);
+        self
+    }
+
+    /// Number of blocks sharing the same difficulty
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Desired time between two blocks, in milliseconds
+    pub fn target_interval(&self) -> u64 {
+        self.target_interval
+    }
+
+    pub fn max_factor(&self) -> u64 {
+        self.max_factor
+    }
+
+    pub fn min_difficulty(&self) -> u32 {
+        self.min_difficulty
+    }
+
+    pub fn max_difficulty(&self) -> u32 {
+        self.max_difficulty
+    }
+
+    /// `true` if the block at `height` starts a new window and gets a new difficulty
+    pub fn is_boundary(&self, height: u64) -> bool {
+        height > self.window && (height - 1).is_multiple_of(self.window)
+    }
+
+    /// Difficulty following `current`, given that the previous window of blocks spanned
+    /// `elapsed` milliseconds from its first to its last timestamp
+    pub fn adjust(&self, current: u32, elapsed: u64) -> u32 {
+        let expected = (self.window - 1) * self.target_interval;
+        let elapsed = elapsed.max(1);
+        let max_step = self.max_factor.ilog2();
+
+        let next = if elapsed < expected {
+            let step = (expected / elapsed).ilog2().min(max_step);
+            current.saturating_add(step)
+        } else {
+            let step = (elapsed / expected.max(1)).ilog2().min(max_step);
+            current.saturating_sub(step)
+        };
+
+        next.clamp(self.min_difficulty, self.max_difficulty)
+    }
+}
+
+/// Proof-of-work: the hash of a header must start with `difficulty` zero bits.
+///
+/// Sealing searches `header.nonce` until the hash meets the target, so every extra bit
+/// of difficulty doubles the expected work. A difficulty of `0` accepts any nonce,
+/// which keeps tests fast. Without a `Retarget` policy the difficulty never changes.
+#[derive(Debug, Clone, Copy)]
+pub struct ProofOfWork {
+    difficulty: u32,
+    retarget: Option<Retarget>,
+}
+
+impl ProofOfWork {
+    /// Proof-of-work starting at `difficulty` for the first block after genesis, capped at
+    /// `MAX_DIFFICULTY`
+    pub fn new(difficulty: u32) -> Self {
+        Self {
+            difficulty: difficulty.min(MAX_DIFFICULTY),
+            retarget: None,
+        }
+    }
+
+    pub fn with_retarget(mut self, retarget: Retarget) -> Self {
+        self.retarget = Some(retarget);
+        self
+    }
+
+    /// Initial difficulty
+    pub fn difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    pub fn retarget(&self) -> Option<&Retarget> {
+        self.retarget.as_ref()
+    }
+
+    /// Difficulty the block at `height`, placed on top of `parent`, must be sealed with
+    pub fn expected_difficulty(
+        &self,
+        height: u64,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<u32, CustomErrs> {
+        let retarget = match self.retarget {
+            Some(retarget) if height > 1 => retarget,
+            _ => return Ok(self.difficulty),
+        };
+
+        if !retarget.is_boundary(height) {
+            return Ok(parent.difficulty);
+        }
+
+        let first = chain.header_at(height - retarget.window)?;
+        let elapsed = parent.timestamp.saturating_sub(first.timestamp);
+        Ok(retarget.adjust(parent.difficulty, elapsed))
+    }
+
+    /// Searches nonces, starting from the header's current one, until its hash under
+    /// `algorithm` has `header.difficulty` leading zero bits.
+    ///
+    /// Fails once every nonce has been tried, leaving the header's nonce where it started.
+    pub fn search_nonce(
+        header: &mut BlockHeader,
+        algorithm: HashAlgorithm,
+    ) -> Result<(), CustomErrs> {
+        let difficulty = header.difficulty;
+        if difficulty > MAX_DIFFICULTY {
+            return Err(CustomErrs::NonceSpaceExhausted { difficulty });
+        }
+
+        let start = header.nonce;
+        while !Self::meets_target(&header.hash(algorithm), difficulty) {
+            header.nonce = header.nonce.wrapping_add(1);
+            if header.nonce == start {
+                return Err(CustomErrs::NonceSpaceExhausted { difficulty });
+            }
+        }
+        Ok(())
+    }
+
+    /// `true` if `hash` starts with at least `difficulty` zero bits
+    pub fn meets_target(hash: &[u8], difficulty: u32) -> bool {
+        leading_zero_bits(hash) >= difficulty
+    }
+}
+
+impl Consensus for ProofOfWork {
+    fn seal(
+        &self,
+        header: &mut BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        header.difficulty = self.expected_difficulty(header.height, parent, chain)?;
+        header.nonce = 0;
+        Self::search_nonce(header, chain.genesis().hash_algorithm)
+    }
+
+    fn validate(
+        &self,
+        header: &BlockHeader,
+        parent: &BlockHeader,
+        chain: &dyn ChainView,
+    ) -> Result<(), CustomErrs> {
+        let expected = self.expected_difficulty(header.height, parent, chain)?;
+        if header.difficulty != expected {
+            return Err(CustomErrs::UnexpectedDifficulty {
+                expected,
+                found: header.difficulty,
+            });
+        }
+
+        if !Self::meets_target(
+            &header.hash(chain.genesis().hash_algorithm),
+            header.difficulty,
+        ) {
+            return Err(CustomErrs::InsufficientWork);
+        }
+
+        Ok(())
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut count = 0;
+    for byte in bytes {
+        count += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    count
+}
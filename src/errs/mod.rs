@@ -1,16 +1,490 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use std::{error::Error, fmt, sync::Arc};
+
+use crate::gen::{Hash, PublicKey};
+
+/// Error raised by another library, kept as the `source()` of a `CustomErrs`.
+///
+/// Two sources are equal if they display the same message.
+#[derive(Clone)]
+pub struct Source(Arc<dyn Error + Send + Sync>);
+
+impl<E: Error + Send + Sync + 'static> From<E> for Source {
+    fn from(error: E) -> Self {
+        Self(Arc::new(error))
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for Source {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CustomErrs {
-    InvalidPublicKey,
-    InvalidPrivateKey,
-    InvalidBlock,
-    VerificationDoesNotMatch,
-    Default,
-    CouldNotInsertRecordsIntoDatabase,
-    CouldNotInsertHashIntoDatabase,
-    CannotEstablishDatabaseConnection,
-    NoSuchTableInDatabase,
-    InvalidSignature,
+    InvalidPublicKey {
+        source: Source,
+    },
+    InvalidPrivateKey {
+        source: Source,
+    },
+    InvalidSignature {
+        source: Source,
+    },
+    VerificationDoesNotMatch {
+        source: Source,
+    },
+    /// A private key does not belong to the public key it was used for
+    KeyMismatch,
+    /// A `SignerSet` needs between 1 and `MAX_SIGNERS` keys and a threshold between 1 and
+    /// its number of keys
+    InvalidSignerSet {
+        signers: usize,
+        threshold: u8,
+    },
+    DuplicateSigner {
+        public_key: PublicKey,
+    },
+    NotASigner {
+        public_key: PublicKey,
+    },
+    /// A signature names a key the signer does not have, or is not in increasing key order
+    InvalidSignerIndex {
+        index: u8,
+    },
+    AlreadySigned {
+        index: u8,
+    },
+    NotEnoughSignatures {
+        threshold: u8,
+        found: usize,
+    },
+    InvalidMnemonic {
+        source: Source,
+    },
+    /// Seed phrases have 12, 15, 18, 21 or 24 words
+    InvalidMnemonicLength {
+        word_count: usize,
+    },
+    InvalidDerivationPath {
+        path: String,
+    },
+    /// Child indexes are below `gen::hd::HARDENED`, which is added by derivation
+    InvalidChildIndex {
+        index: u32,
+    },
+    /// Key labels name files in a `keystore::KeyStore`, see `KeyStore::save()`
+    InvalidKeyLabel {
+        label: String,
+    },
+    NoSuchKey {
+        label: String,
+    },
+    KeyAlreadyExists {
+        label: String,
+    },
+    /// The passphrase does not decrypt the key file, or the file was tampered with
+    WrongPassphrase,
+    KeyDerivationFailed {
+        source: Source,
+    },
+    CouldNotAccessKeyStore {
+        path: String,
+        source: Source,
+    },
+    CorruptedKeyFile {
+        path: String,
+        source: Source,
+    },
+    /// Key file written by another version of `keystore`
+    UnsupportedKeyFileVersion {
+        found: u8,
+    },
+    /// The record at `index` of a block was rejected
+    InvalidRecord {
+        index: usize,
+        source: Box<CustomErrs>,
+    },
+    /// The block at `index` of an imported batch was rejected
+    InvalidBlock {
+        index: usize,
+        source: Box<CustomErrs>,
+    },
+    CouldNotStartThreadPool {
+        source: Source,
+    },
+    /// A value has no binary or JSON form, e.g. a record holding a map with non-string keys
+    CouldNotSerialize {
+        source: Source,
+    },
+    CouldNotDeserialize {
+        source: Source,
+    },
+    /// A record was signed for another chain
+    WrongChain {
+        expected: Hash,
+        found: Hash,
+    },
+    /// A record signature covers a payload version this build does not know
+    UnsupportedSigningVersion {
+        found: u8,
+    },
+    /// Encoded value written by another version of `codec`
+    UnsupportedCodecVersion {
+        found: u8,
+    },
+    CouldNotInsertRecordsIntoDatabase {
+        source: Source,
+    },
+    CouldNotInsertHashIntoDatabase {
+        source: Source,
+    },
+    CannotEstablishDatabaseConnection {
+        path: String,
+        source: Source,
+    },
+    NoSuchTableInDatabase {
+        table: String,
+    },
     EmptyBlocksNotAllowed,
-    NoDatabaseConnected,
-    CannotCreateSuchTable,
+    CannotCreateSuchTable {
+        table: String,
+        source: Source,
+    },
+    CouldNotReadFromDatabase {
+        table: String,
+        source: Source,
+    },
+    CouldNotInsertRowIntoDatabase {
+        table: String,
+        source: Source,
+    },
+    NoSuchRowInTable {
+        table: String,
+        position: i64,
+    },
+    NoSuchColumnInTable {
+        table: String,
+        column: String,
+    },
+    /// A row does not have one value per column of its table
+    ColumnCountMismatch {
+        table: String,
+        expected: usize,
+        found: usize,
+    },
+    CorruptedDatabaseRow {
+        table: String,
+        source: Source,
+    },
+    /// A log file does not hold a valid frame at `offset`, see `utils::LogDB`
+    CorruptedLogFile {
+        path: String,
+        offset: u64,
+    },
+    TransactionAlreadyInProgress,
+    NoTransactionInProgress,
+    DatabaseTransactionFailed {
+        source: Source,
+    },
+    /// The chain holds no block, not even its genesis block
+    EmptyChain,
+    NoSuchBlockInDatabase {
+        hash: Hash,
+    },
+    NoBlockAtHeight {
+        height: i64,
+    },
+    InvalidHash {
+        len: usize,
+    },
+    /// Text is not the lowercase hex form of a `gen::Hash`
+    CouldNotParseHash {
+        source: Source,
+    },
+    UnknownHashAlgorithm {
+        name: String,
+    },
+    PreviousHashMismatch {
+        expected: Hash,
+        found: Hash,
+    },
+    InvalidBlockHeight {
+        expected: u64,
+        found: u64,
+    },
+    RecordCountMismatch {
+        expected: u64,
+        found: u64,
+    },
+    RecordsRootMismatch,
+    TimestampBeforeParent,
+    /// A header's timestamp is further ahead of the local clock than allowed
+    TimestampInFuture {
+        timestamp: u64,
+        limit: u64,
+    },
+    GenesisMismatch {
+        expected: Hash,
+        found: Hash,
+    },
+    InsufficientWork,
+    UnexpectedDifficulty {
+        expected: u32,
+        found: u32,
+    },
+    /// No nonce gives a header hash meeting `difficulty`
+    NonceSpaceExhausted {
+        difficulty: u32,
+    },
+    NoSealingKey,
+    WrongSealer,
+    InvalidSeal,
+    InvalidGovernanceRecord,
+    InsufficientGovernanceApprovals,
+    DuplicateRecord,
+    MemPoolFull,
+    /// A record failed the checks of `Record::validate()` or `Record::validate_against()`
+    RecordRejected {
+        reason: String,
+    },
+    ReplayedNonce {
+        expected: u64,
+        found: u64,
+    },
+    NonceGap {
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl fmt::Display for CustomErrs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicKey { .. } => write!(f, "invalid public key"),
+            Self::InvalidPrivateKey { .. } => write!(f, "invalid private key"),
+            Self::InvalidSignature { .. } => write!(f, "invalid signature"),
+            Self::VerificationDoesNotMatch { .. } => {
+                write!(f, "signature does not match the message")
+            }
+            Self::KeyMismatch => write!(f, "private key does not match the public key"),
+            Self::InvalidSignerSet { signers, threshold } => write!(
+                f,
+                "invalid signer set of {} keys with threshold {}",
+                signers, threshold
+            ),
+            Self::DuplicateSigner { public_key } => {
+                write!(f, "key {} appears twice in the signer set", public_key)
+            }
+            Self::NotASigner { public_key } => {
+                write!(f, "key {} is not part of the signer set", public_key)
+            }
+            Self::InvalidSignerIndex { index } => {
+                write!(f, "unexpected signature of signer {}", index)
+            }
+            Self::AlreadySigned { index } => write!(f, "signer {} already signed", index),
+            Self::NotEnoughSignatures { threshold, found } => {
+                write!(f, "{} signatures found, {} are required", found, threshold)
+            }
+            Self::InvalidMnemonic { .. } => write!(f, "invalid seed phrase"),
+            Self::InvalidMnemonicLength { word_count } => {
+                write!(f, "seed phrases cannot have {} words", word_count)
+            }
+            Self::InvalidDerivationPath { path } => write!(f, "invalid derivation path {}", path),
+            Self::InvalidChildIndex { index } => write!(f, "invalid child index {}", index),
+            Self::InvalidKeyLabel { label } => write!(f, "invalid key label {:?}", label),
+            Self::NoSuchKey { label } => write!(f, "no key labelled {} in the keystore", label),
+            Self::KeyAlreadyExists { label } => {
+                write!(f, "a key labelled {} is already in the keystore", label)
+            }
+            Self::WrongPassphrase => write!(f, "wrong passphrase or tampered key file"),
+            Self::KeyDerivationFailed { .. } => {
+                write!(f, "could not derive a key from the passphrase")
+            }
+            Self::CouldNotAccessKeyStore { path, .. } => {
+                write!(f, "could not access keystore file {}", path)
+            }
+            Self::CorruptedKeyFile { path, .. } => write!(f, "corrupted key file {}", path),
+            Self::UnsupportedKeyFileVersion { found } => {
+                write!(f, "unsupported key file version {}", found)
+            }
+            Self::InvalidRecord { index, .. } => write!(f, "invalid record at index {}", index),
+            Self::InvalidBlock { index, .. } => write!(f, "invalid block at index {}", index),
+            Self::CouldNotStartThreadPool { .. } => write!(f, "could not start thread pool"),
+            Self::CouldNotSerialize { .. } => write!(f, "could not serialize value"),
+            Self::CouldNotDeserialize { .. } => write!(f, "could not deserialize value"),
+            Self::WrongChain { expected, found } => write!(
+                f,
+                "record signed for chain {}, expected {}",
+                found, expected
+            ),
+            Self::UnsupportedSigningVersion { found } => {
+                write!(f, "unsupported signing payload version {}", found)
+            }
+            Self::UnsupportedCodecVersion { found } => {
+                write!(f, "unsupported codec version {}", found)
+            }
+            Self::CouldNotInsertRecordsIntoDatabase { .. } => {
+                write!(f, "could not insert records into the database")
+            }
+            Self::CouldNotInsertHashIntoDatabase { .. } => {
+                write!(f, "could not insert block hash into the database")
+            }
+            Self::CannotEstablishDatabaseConnection { path, .. } => {
+                write!(f, "cannot open database at {}", path)
+            }
+            Self::NoSuchTableInDatabase { table } => write!(f, "no table {} in database", table),
+            Self::EmptyBlocksNotAllowed => write!(f, "empty blocks are not allowed"),
+            Self::CannotCreateSuchTable { table, .. } => {
+                write!(f, "cannot create table {}", table)
+            }
+            Self::CouldNotReadFromDatabase { table, .. } => {
+                write!(f, "could not read from table {}", table)
+            }
+            Self::CouldNotInsertRowIntoDatabase { table, .. } => {
+                write!(f, "could not insert row into table {}", table)
+            }
+            Self::NoSuchRowInTable { table, position } => {
+                write!(f, "no row at position {} in table {}", position, table)
+            }
+            Self::NoSuchColumnInTable { table, column } => {
+                write!(f, "no column {} in table {}", column, table)
+            }
+            Self::ColumnCountMismatch {
+                table,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row of table {} has {} columns, expected {}",
+                table, found, expected
+            ),
+            Self::CorruptedDatabaseRow { table, .. } => {
+                write!(f, "corrupted row in table {}", table)
+            }
+            Self::CorruptedLogFile { path, offset } => {
+                write!(f, "corrupted log file {} at offset {}", path, offset)
+            }
+            Self::TransactionAlreadyInProgress => write!(f, "a transaction is already in progress"),
+            Self::NoTransactionInProgress => write!(f, "no transaction in progress"),
+            Self::DatabaseTransactionFailed { .. } => write!(f, "database transaction failed"),
+            Self::EmptyChain => write!(f, "the chain holds no block"),
+            Self::NoSuchBlockInDatabase { hash } => {
+                write!(f, "no block with hash {} in database", hash)
+            }
+            Self::NoBlockAtHeight { height } => write!(f, "no block at height {}", height),
+            Self::InvalidHash { len } => write!(f, "invalid hash of {} bytes", len),
+            Self::CouldNotParseHash { .. } => write!(f, "could not parse hex hash"),
+            Self::UnknownHashAlgorithm { name } => write!(f, "unknown hash algorithm {}", name),
+            Self::PreviousHashMismatch { expected, found } => {
+                write!(f, "block extends {}, expected the tip {}", found, expected)
+            }
+            Self::InvalidBlockHeight { expected, found } => {
+                write!(f, "block has height {}, expected {}", found, expected)
+            }
+            Self::RecordCountMismatch { expected, found } => write!(
+                f,
+                "header counts {} records, block holds {}",
+                found, expected
+            ),
+            Self::RecordsRootMismatch => write!(f, "header does not commit to the block's records"),
+            Self::TimestampBeforeParent => write!(f, "block is older than its parent"),
+            Self::TimestampInFuture { timestamp, limit } => write!(
+                f,
+                "block timestamp {} is past the latest accepted one, {}",
+                timestamp, limit
+            ),
+            Self::GenesisMismatch { expected, found } => write!(
+                f,
+                "stored genesis block {} is not the expected {}",
+                found, expected
+            ),
+            Self::InsufficientWork => write!(f, "block hash does not meet its difficulty"),
+            Self::UnexpectedDifficulty { expected, found } => {
+                write!(f, "block has difficulty {}, expected {}", found, expected)
+            }
+            Self::NonceSpaceExhausted { difficulty } => {
+                write!(f, "no nonce meets difficulty {}", difficulty)
+            }
+            Self::NoSealingKey => write!(f, "no key to seal blocks with"),
+            Self::WrongSealer => write!(f, "block sealed by the wrong authority"),
+            Self::InvalidSeal => write!(f, "invalid block seal"),
+            Self::InvalidGovernanceRecord => write!(f, "invalid governance record"),
+            Self::InsufficientGovernanceApprovals => {
+                write!(f, "governance record lacks a majority of approvals")
+            }
+            Self::DuplicateRecord => write!(f, "record is already present"),
+            Self::MemPoolFull => write!(f, "memory pool is full"),
+            Self::RecordRejected { reason } => write!(f, "record rejected: {}", reason),
+            Self::ReplayedNonce { expected, found } => {
+                write!(f, "nonce {} was already used, expected {}", found, expected)
+            }
+            Self::NonceGap { expected, found } => {
+                write!(f, "nonce {} skips ahead, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl Error for CustomErrs {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidPublicKey { source }
+            | Self::InvalidPrivateKey { source }
+            | Self::InvalidSignature { source }
+            | Self::VerificationDoesNotMatch { source }
+            | Self::CouldNotSerialize { source }
+            | Self::CouldNotDeserialize { source }
+            | Self::CouldNotParseHash { source }
+            | Self::InvalidMnemonic { source }
+            | Self::KeyDerivationFailed { source }
+            | Self::CouldNotAccessKeyStore { source, .. }
+            | Self::CorruptedKeyFile { source, .. }
+            | Self::CouldNotInsertRecordsIntoDatabase { source }
+            | Self::CouldNotInsertHashIntoDatabase { source }
+            | Self::CannotEstablishDatabaseConnection { source, .. }
+            | Self::CannotCreateSuchTable { source, .. }
+            | Self::CouldNotReadFromDatabase { source, .. }
+            | Self::CouldNotInsertRowIntoDatabase { source, .. }
+            | Self::CorruptedDatabaseRow { source, .. }
+            | Self::DatabaseTransactionFailed { source }
+            | Self::CouldNotStartThreadPool { source } => Some(&*source.0),
+            Self::InvalidRecord { source, .. } | Self::InvalidBlock { source, .. } => {
+                Some(&**source)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl CustomErrs {
+    /// Wraps this error as the reason the record at `index` of a block was rejected
+    pub fn in_record(self, index: usize) -> Self {
+        Self::InvalidRecord {
+            index,
+            source: Box::new(self),
+        }
+    }
+
+    /// Wraps this error as the reason the block at `index` of an imported batch was rejected
+    pub fn in_block(self, index: usize) -> Self {
+        Self::InvalidBlock {
+            index,
+            source: Box::new(self),
+        }
+    }
 }
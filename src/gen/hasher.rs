@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::errs::CustomErrs;
+
+use super::Hash;
+
+/// Length in bytes of the digests of every supported algorithm
+pub const HASH_LEN: usize = 32;
+
+/// A hash function producing `HASH_LEN` byte digests.
+///
+/// Every implementation is named by a `HashAlgorithm`, which is what a chain stores in its
+/// `Genesis` to know how its blocks are hashed.
+pub trait Hasher {
+    const ALGORITHM: HashAlgorithm;
+
+    fn digest(bytes: &[u8]) -> [u8; HASH_LEN];
+
+    /// Hashes `bytes` into a `Hash` tagged with `Self::ALGORITHM`
+    fn hash(bytes: &[u8]) -> Hash {
+        Hash::new(Self::ALGORITHM, Self::digest(bytes))
+    }
+}
+
+/// SHA-256, the algorithm used when none is configured
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256;
+
+/// SHA-512 truncated to 256 bits, faster than SHA-256 on 64-bit machines
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha512_256;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha3_256;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake3;
+
+impl Hasher for Sha256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+    fn digest(bytes: &[u8]) -> [u8; HASH_LEN] {
+        sha2::Sha256::digest(bytes).into()
+    }
+}
+
+impl Hasher for Sha512_256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha512_256;
+
+    fn digest(bytes: &[u8]) -> [u8; HASH_LEN] {
+        sha2::Sha512_256::digest(bytes).into()
+    }
+}
+
+impl Hasher for Sha3_256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha3_256;
+
+    fn digest(bytes: &[u8]) -> [u8; HASH_LEN] {
+        sha3::Sha3_256::digest(bytes).into()
+    }
+}
+
+impl Hasher for Blake3 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Blake3;
+
+    fn digest(bytes: &[u8]) -> [u8; HASH_LEN] {
+        blake3::hash(bytes).into()
+    }
+}
+
+/// Names one of the `Hasher` implementations, e.g. in a `Genesis`
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512_256,
+    Sha3_256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    const ALL: [Self; 4] = [Self::Sha256, Self::Sha512_256, Self::Sha3_256, Self::Blake3];
+
+    /// Lowercase name of the algorithm, as in the text form of a `Hash`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512_256 => "sha512-256",
+            Self::Sha3_256 => "sha3-256",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    /// The algorithm called `name`, see `name()`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    /// Hashes raw bytes as they are, without serializing them first
+    pub fn digest(self, bytes: &[u8]) -> Hash {
+        match self {
+            Self::Sha256 => Sha256::hash(bytes),
+            Self::Sha512_256 => Sha512_256::hash(bytes),
+            Self::Sha3_256 => Sha3_256::hash(bytes),
+            Self::Blake3 => Blake3::hash(bytes),
+        }
+    }
+
+    /// Hashes the bincode encoding of `data`
+    pub fn encrypt<T: Serialize>(self, data: &T) -> Result<Hash, CustomErrs> {
+        let bytes = bincode::serialize(data)
+            .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })?;
+        Ok(self.digest(&bytes))
+    }
+}
@@ -0,0 +1,205 @@
+//! Deterministic ed25519 keys derived from a BIP-39 seed phrase, following SLIP-0010.
+//!
+//! Writing down the seed phrase once is enough to restore every key derived from it.
+//! ed25519 only supports hardened derivation, so every step of a `DerivationPath` is
+//! hardened.
+
+use std::{fmt, str::FromStr};
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+use crate::errs::CustomErrs;
+
+use super::{KeyPair, PrivateKey};
+
+/// Bit set on the index of a hardened child
+pub const HARDENED: u32 = 1 << 31;
+
+/// Key of the HMAC computing the master key from the seed, see SLIP-0010
+const ED25519_CURVE: &[u8] = b"ed25519 seed";
+
+/// BIP-39 seed phrase in English.
+///
+/// It has no `Debug` or `Display` implementation so that it never ends up in logs;
+/// use `phrase()` to show it to its owner.
+pub struct Mnemonic(bip39::Mnemonic);
+
+impl Mnemonic {
+    /// Draws a new phrase of 12, 15, 18, 21 or 24 words
+    pub fn generate(word_count: usize) -> Result<Self, CustomErrs> {
+        if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+            return Err(CustomErrs::InvalidMnemonicLength { word_count });
+        }
+
+        let mut entropy = Zeroizing::new(vec![0; word_count / 3 * 4]);
+        rand::rngs::OsRng.fill_bytes(&mut entropy);
+        Self::from_entropy(&entropy)
+    }
+
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, CustomErrs> {
+        bip39::Mnemonic::from_entropy(entropy)
+            .map(Self)
+            .map_err(|err| CustomErrs::InvalidMnemonic { source: err.into() })
+    }
+
+    /// Parses a phrase, checking its words and checksum
+    pub fn parse(phrase: &str) -> Result<Self, CustomErrs> {
+        bip39::Mnemonic::parse_normalized(phrase)
+            .map(Self)
+            .map_err(|err| CustomErrs::InvalidMnemonic { source: err.into() })
+    }
+
+    pub fn phrase(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.0.to_string())
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.0.word_count()
+    }
+
+    /// 64 byte seed of this phrase protected by `passphrase`, which may be empty
+    pub fn to_seed(&self, passphrase: &str) -> Zeroizing<[u8; 64]> {
+        Zeroizing::new(self.0.to_seed_normalized(passphrase))
+    }
+
+    /// Master key of the tree derived from this phrase and `passphrase`
+    pub fn master_key(&self, passphrase: &str) -> ExtendedKey {
+        ExtendedKey::from_seed(self.to_seed(passphrase).as_ref())
+    }
+}
+
+/// Sequence of hardened child indexes leading from the master key to a derived key.
+///
+/// Its text form is `m` followed by one `/<index>'` or `/<index>H` per step, e.g.
+/// `m/44'/0'/1'`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Path of the master key itself
+    pub fn master() -> Self {
+        Self::default()
+    }
+
+    /// `m/44'/<coin_type>'/<account>'`, the BIP-44 layout used for one account per key
+    pub fn account(coin_type: u32, account: u32) -> Result<Self, CustomErrs> {
+        Self::master().child(44)?.child(coin_type)?.child(account)
+    }
+
+    /// This path extended by the hardened child `index`
+    pub fn child(mut self, index: u32) -> Result<Self, CustomErrs> {
+        if index >= HARDENED {
+            return Err(CustomErrs::InvalidChildIndex { index });
+        }
+        self.0.push(index | HARDENED);
+        Ok(self)
+    }
+
+    /// Indexes of every step, with the `HARDENED` bit set
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for index in &self.0 {
+            write!(f, "/{}'", index & !HARDENED)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = CustomErrs;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || CustomErrs::InvalidDerivationPath {
+            path: text.to_owned(),
+        };
+
+        let mut steps = text.split('/');
+        if steps.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        steps.try_fold(Self::master(), |path, step| {
+            let index = step
+                .strip_suffix('\'')
+                .or_else(|| step.strip_suffix('H'))
+                .ok_or_else(invalid)?;
+            if !index.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            path.child(index.parse().map_err(|_| invalid())?)
+        })
+    }
+}
+
+/// Node of a SLIP-0010 ed25519 tree: a private key and the chain code deriving its
+/// children
+#[derive(Clone)]
+pub struct ExtendedKey {
+    private_key: PrivateKey,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedKey {
+    /// Master key of the tree grown from `seed`, e.g. `Mnemonic::to_seed()`
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Self::from_hmac(ED25519_CURVE, &[seed])
+    }
+
+    /// Hardened child `index` of this key, written `index'` in a path
+    pub fn derive_child(&self, index: u32) -> Result<Self, CustomErrs> {
+        if index >= HARDENED {
+            return Err(CustomErrs::InvalidChildIndex { index });
+        }
+        Ok(Self::from_hmac(
+            self.chain_code.as_ref(),
+            &[
+                &[0],
+                self.private_key.as_bytes(),
+                &(index | HARDENED).to_be_bytes(),
+            ],
+        ))
+    }
+
+    /// Key at `path` below this one
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, CustomErrs> {
+        path.indexes().iter().try_fold(self.clone(), |key, index| {
+            key.derive_child(index & !HARDENED)
+        })
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn key_pair(&self) -> KeyPair {
+        KeyPair::from_private_key(self.private_key.clone())
+    }
+
+    /// Splits HMAC-SHA512(`key`, `data`) into a private key and a chain code
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+        data.iter().for_each(|part| mac.update(part));
+        let output = Zeroizing::new(<[u8; 64]>::from(mac.finalize().into_bytes()));
+
+        let mut chain_code = Zeroizing::new([0; 32]);
+        chain_code.copy_from_slice(&output[32..]);
+        Self {
+            // Any 32 bytes make a valid ed25519 private key
+            private_key: PrivateKey::from_bytes(&output[..32]).unwrap(),
+            chain_code,
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use std::{error::Error, fmt, iter, str::FromStr};
+
+use curve25519_dalek::{
+    constants::ED25519_BASEPOINT_POINT,
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::Scalar,
+    traits::{IsIdentity, VartimeMultiscalarMul},
+};
+use ed25519_dalek::Signer;
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::errs::{CustomErrs, Source};
+
+pub const PUBLIC_KEY_LEN: usize = 32;
+pub const PRIVATE_KEY_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+
+/// ed25519 public key, identifying the signer of records and blocks.
+///
+/// Only canonical encodings are accepted; whether they are a curve point is left to
+/// `verify()`, so that parsing stays cheap. Its text form, used by `Display`, `FromStr` and human-readable serde formats, is
+/// lowercase hex. `to_base58()` gives a shorter form for display to users.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PublicKey([u8; PUBLIC_KEY_LEN]);
+
+/// ed25519 private key. Its bytes are wiped from memory when it is dropped.
+///
+/// It has no `Debug` or `Display` implementation so that it never ends up in logs;
+/// use the explicit `to_hex()` or `to_base58()` to export it.
+#[derive(Clone)]
+pub struct PrivateKey([u8; PRIVATE_KEY_LEN]);
+
+/// ed25519 signature, encoded like `PublicKey`.
+///
+/// Its `R` point, the first 32 bytes, obeys the same rules as a `PublicKey`, and its
+/// scalar `s`, the last 32 bytes, must be reduced.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Signature([u8; SIGNATURE_LEN]);
+
+/// A private key along with the public key derived from it
+#[derive(Clone)]
+pub struct KeyPair {
+    private_key: PrivateKey,
+    public_key: PublicKey,
+}
+
+impl PublicKey {
+    /// Parses the 32 bytes of a public key, rejecting encodings that are not canonical, see
+    /// `is_canonical_point()`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CustomErrs> {
+        let bytes: [u8; PUBLIC_KEY_LEN] = bytes.try_into().map_err(|_| {
+            public_key_error(EncodingError::Length(PUBLIC_KEY_LEN, bytes.len()).into())
+        })?;
+        if !is_canonical_point(&bytes) {
+            return Err(public_key_error(EncodingError::NonCanonicalPoint.into()));
+        }
+        Ok(Self(bytes))
+    }
+
+    pub fn from_base58(text: &str) -> Result<Self, CustomErrs> {
+        Self::from_bytes(&decode_base58(text, public_key_error)?)
+    }
+
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.0).into_string()
+    }
+
+    pub fn as_bytes(&self) -> &[u8; PUBLIC_KEY_LEN] {
+        &self.0
+    }
+
+    /// Checks that `signature` was made over `msg` by the private key of this public key.
+    ///
+    /// The check is cofactored, `[8][s]B = [8]R + [8][k]A`, like the one of `verify_batch()`.
+    pub fn verify(&self, msg: &[u8], signature: &Signature) -> Result<(), CustomErrs> {
+        let point = self.point()?;
+        let r = signature.r()?;
+        let k = challenge(self, msg, signature);
+        let check =
+            EdwardsPoint::vartime_double_scalar_mul_basepoint(&k, &-point, &signature.s()) - r;
+        match check.mul_by_cofactor().is_identity() {
+            true => Ok(()),
+            false => Err(verification_error()),
+        }
+    }
+
+    fn point(&self) -> Result<EdwardsPoint, CustomErrs> {
+        CompressedEdwardsY(self.0)
+            .decompress()
+            .ok_or_else(|| public_key_error(EncodingError::NotAPoint.into()))
+    }
+}
+
+impl PrivateKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CustomErrs> {
+        let secret = ed25519_dalek::SecretKey::from_bytes(bytes)
+            .map_err(|err| CustomErrs::InvalidPrivateKey { source: err.into() })?;
+        Ok(Self(secret.to_bytes()))
+    }
+
+    pub fn from_hex(text: &str) -> Result<Self, CustomErrs> {
+        let bytes = Zeroizing::new(decode_hex(text, private_key_error)?);
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_base58(text: &str) -> Result<Self, CustomErrs> {
+        let bytes = Zeroizing::new(decode_base58(text, private_key_error)?);
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(self.0))
+    }
+
+    pub fn to_base58(&self) -> Zeroizing<String> {
+        Zeroizing::new(bs58::encode(self.0).into_string())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; PRIVATE_KEY_LEN] {
+        &self.0
+    }
+
+    /// Derives the public key matching this private key
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(ed25519_dalek::PublicKey::from(&self.secret()).to_bytes())
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Signature {
+        let secret = self.secret();
+        let keypair = ed25519_dalek::Keypair {
+            public: ed25519_dalek::PublicKey::from(&secret),
+            secret,
+        };
+        Signature(keypair.sign(msg).to_bytes())
+    }
+
+    fn secret(&self) -> ed25519_dalek::SecretKey {
+        // A `PrivateKey` only ever holds bytes accepted by `SecretKey::from_bytes()`
+        ed25519_dalek::SecretKey::from_bytes(&self.0).unwrap()
+    }
+}
+
+/// Checks in a single batch that each `signatures[i]` was made over `messages[i]` by the
+/// private key of `public_keys[i]`.
+///
+/// This is much faster than verifying each signature on its own, but an error does not
+/// tell which signature is invalid. Both checks are cofactored, so that points with a
+/// small-order component cannot make them disagree: the batch succeeds exactly when
+/// `PublicKey::verify()` succeeds for every signature, but for a chance of 2^-128.
+pub fn verify_batch(
+    public_keys: &[PublicKey],
+    messages: &[&[u8]],
+    signatures: &[Signature],
+) -> Result<(), CustomErrs> {
+    if public_keys.len() != signatures.len() || messages.len() != signatures.len() {
+        return Err(CustomErrs::VerificationDoesNotMatch {
+            source: EncodingError::Length(signatures.len(), messages.len().max(public_keys.len()))
+                .into(),
+        });
+    }
+    let points = public_keys
+        .iter()
+        .map(PublicKey::point)
+        .collect::<Result<Vec<_>, _>>()?;
+    let rs = signatures
+        .iter()
+        .map(Signature::r)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Each equation is weighed by a random `z`, so that they cannot cancel each other out
+    let mut rng = rand::thread_rng();
+    let zs: Vec<Scalar> = signatures
+        .iter()
+        .map(|_| Scalar::from(rng.gen::<u128>()))
+        .collect();
+    let basepoint_scalar: Scalar = signatures
+        .iter()
+        .zip(&zs)
+        .map(|(signature, z)| z * signature.s())
+        .sum();
+    let zks = public_keys
+        .iter()
+        .zip(messages)
+        .zip(signatures)
+        .zip(&zs)
+        .map(|(((public_key, msg), signature), z)| z * challenge(public_key, msg, signature));
+
+    // [8](-∑ z s B + ∑ z R + ∑ z k A) is the identity when every equation holds
+    let check = EdwardsPoint::vartime_multiscalar_mul(
+        iter::once(-basepoint_scalar)
+            .chain(zs.iter().copied())
+            .chain(zks),
+        iter::once(ED25519_BASEPOINT_POINT).chain(rs).chain(points),
+    );
+    match check.mul_by_cofactor().is_identity() {
+        true => Ok(()),
+        false => Err(verification_error()),
+    }
+}
+
+/// `k = H(R || A || msg)`, the scalar by which a signature binds the key and the message
+fn challenge(public_key: &PublicKey, msg: &[u8], signature: &Signature) -> Scalar {
+    let mut hash = [0; 64];
+    hash.copy_from_slice(
+        &Sha512::new()
+            .chain_update(&signature.0[..PUBLIC_KEY_LEN])
+            .chain_update(public_key.0)
+            .chain_update(msg)
+            .finalize(),
+    );
+    Scalar::from_bytes_mod_order_wide(&hash)
+}
+
+fn verification_error() -> CustomErrs {
+    CustomErrs::VerificationDoesNotMatch {
+        source: EncodingError::EquationFailed.into(),
+    }
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Signature {
+    /// Parses the 64 bytes of a signature, rejecting an `R` or `s` that is not canonical
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CustomErrs> {
+        let bytes: [u8; SIGNATURE_LEN] = bytes.try_into().map_err(|_| {
+            signature_error(EncodingError::Length(SIGNATURE_LEN, bytes.len()).into())
+        })?;
+        let signature = Self(bytes);
+        if !is_canonical_point(&signature.r_bytes()) {
+            return Err(signature_error(EncodingError::NonCanonicalPoint.into()));
+        }
+        if Scalar::from_canonical_bytes(signature.s_bytes()).is_none() {
+            return Err(signature_error(EncodingError::NonCanonicalScalar.into()));
+        }
+        Ok(signature)
+    }
+
+    pub fn from_base58(text: &str) -> Result<Self, CustomErrs> {
+        Self::from_bytes(&decode_base58(text, signature_error)?)
+    }
+
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.0).into_string()
+    }
+
+    pub fn as_bytes(&self) -> &[u8; SIGNATURE_LEN] {
+        &self.0
+    }
+
+    fn r_bytes(&self) -> [u8; PUBLIC_KEY_LEN] {
+        let mut r = [0; PUBLIC_KEY_LEN];
+        r.copy_from_slice(&self.0[..PUBLIC_KEY_LEN]);
+        r
+    }
+
+    fn s_bytes(&self) -> [u8; 32] {
+        let mut s = [0; 32];
+        s.copy_from_slice(&self.0[PUBLIC_KEY_LEN..]);
+        s
+    }
+
+    fn r(&self) -> Result<EdwardsPoint, CustomErrs> {
+        CompressedEdwardsY(self.r_bytes())
+            .decompress()
+            .ok_or_else(|| signature_error(EncodingError::NotAPoint.into()))
+    }
+
+    fn s(&self) -> Scalar {
+        // `from_bytes()` only accepts reduced scalars
+        Scalar::from_bits(self.s_bytes())
+    }
+}
+
+impl KeyPair {
+    pub fn generate() -> Self {
+        let mut csprng = rand::rngs::OsRng;
+        let keypair = ed25519_dalek::Keypair::generate(&mut csprng);
+        Self::from_private_key(PrivateKey(keypair.secret.to_bytes()))
+    }
+
+    pub fn from_private_key(private_key: PrivateKey) -> Self {
+        Self {
+            public_key: private_key.public_key(),
+            private_key,
+        }
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Signature {
+        self.private_key.sign(msg)
+    }
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PublicKey({})", self)
+    }
+}
+
+impl fmt::Debug for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Signature({})", self)
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = CustomErrs;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&decode_hex(text, public_key_error)?)
+    }
+}
+
+impl FromStr for Signature {
+    type Err = CustomErrs;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&decode_hex(text, signature_error)?)
+    }
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self, &self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_bytes(deserializer)
+    }
+}
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self, &self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_bytes(deserializer)
+    }
+}
+
+/// Hex string in human-readable formats, plain bytes otherwise
+fn serialize_bytes<S: Serializer>(
+    text: &impl fmt::Display,
+    bytes: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.collect_str(text)
+    } else {
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+fn deserialize_bytes<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = CustomErrs> + TryFrom<Vec<u8>, Error = CustomErrs>,
+{
+    if deserializer.is_human_readable() {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    } else {
+        T::try_from(Vec::<u8>::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+impl TryFrom<Vec<u8>> for PublicKey {
+    type Error = CustomErrs;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Signature {
+    type Error = CustomErrs;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Why bytes were rejected as a key or signature, or a signature did not verify
+#[derive(Debug)]
+enum EncodingError {
+    /// Expected and found number of bytes
+    Length(usize, usize),
+    NonCanonicalPoint,
+    NonCanonicalScalar,
+    NotAPoint,
+    EquationFailed,
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length(expected, found) => {
+                write!(f, "expected {} bytes, found {}", expected, found)
+            }
+            Self::NonCanonicalPoint => f.write_str("not the canonical encoding of a point"),
+            Self::NonCanonicalScalar => f.write_str("scalar is not reduced"),
+            Self::NotAPoint => f.write_str("not a point of the curve"),
+            Self::EquationFailed => f.write_str("verification equation does not hold"),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+/// `false` if `bytes` encodes `y` without reducing it modulo `p = 2^255 - 19`, or sets the
+/// sign of `x` on one of the two points whose `x` is zero.
+///
+/// This only looks at the bytes; whether they are a point is checked when verifying.
+fn is_canonical_point(bytes: &[u8; PUBLIC_KEY_LEN]) -> bool {
+    let negative_x = bytes[31] & 0x80 != 0;
+    // `y` is little endian, without the sign bit, and at least `p - 1` only if its bits
+    // above the lowest byte are all set
+    let high_bits_set = bytes[1..31].iter().all(|&byte| byte == 0xff) && bytes[31] & 0x7f == 0x7f;
+    let reduced = !(high_bits_set && bytes[0] >= 0xed);
+    let y_is_one =
+        bytes[0] == 1 && bytes[1..31].iter().all(|&byte| byte == 0) && bytes[31] & 0x7f == 0;
+    let x_is_zero = y_is_one || (high_bits_set && bytes[0] == 0xec);
+    reduced && !(x_is_zero && negative_x)
+}
+
+fn decode_hex(text: &str, error: fn(Source) -> CustomErrs) -> Result<Vec<u8>, CustomErrs> {
+    hex::decode(text).map_err(|err| error(err.into()))
+}
+
+fn decode_base58(text: &str, error: fn(Source) -> CustomErrs) -> Result<Vec<u8>, CustomErrs> {
+    bs58::decode(text)
+        .into_vec()
+        .map_err(|err| error(err.into()))
+}
+
+fn public_key_error(source: Source) -> CustomErrs {
+    CustomErrs::InvalidPublicKey { source }
+}
+
+fn private_key_error(source: Source) -> CustomErrs {
+    CustomErrs::InvalidPrivateKey { source }
+}
+
+fn signature_error(source: Source) -> CustomErrs {
+    CustomErrs::InvalidSignature { source }
+}
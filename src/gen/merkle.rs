@@ -0,0 +1,220 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errs::CustomErrs;
+
+use super::{Hash, HashAlgorithm, HASH_LEN};
+
+/// Prefix of the bytes hashed for a leaf, so a leaf can never be mistaken for an inner node
+const LEAF_PREFIX: u8 = 0x00;
+/// Prefix of the bytes hashed for an inner node
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hashes a single item into a leaf of the tree
+pub fn leaf_hash<T: Serialize>(algorithm: HashAlgorithm, item: &T) -> Result<Hash, CustomErrs> {
+    let mut bytes = vec![LEAF_PREFIX];
+    bincode::serialize_into(&mut bytes, item)
+        .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })?;
+    Ok(algorithm.digest(&bytes))
+}
+
+fn node_hash(algorithm: HashAlgorithm, left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = Vec::with_capacity(1 + 2 * HASH_LEN);
+    bytes.push(NODE_PREFIX);
+    bytes.extend_from_slice(left);
+    bytes.extend_from_slice(right);
+    algorithm.digest(&bytes)
+}
+
+/// Node one level up from `pair`, a node and its sibling or a node left without one
+fn parent_hash(algorithm: HashAlgorithm, pair: &[Hash]) -> Hash {
+    match pair {
+        [left, right] => node_hash(algorithm, left, right),
+        [single] => *single,
+        _ => unreachable!(),
+    }
+}
+
+/// Which side of the path a sibling hash sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Binary Merkle tree over a list of leaf hashes.
+///
+/// A node without a sibling is carried up to the next level unchanged rather than
+/// being paired with itself, so no two different leaf lists share a root.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    algorithm: HashAlgorithm,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree from leaves already hashed with `algorithm`, see `leaf_hash()`
+    pub fn new(algorithm: HashAlgorithm, leaves: Vec<Hash>) -> Self {
+        Self::grow(algorithm, leaves, |level| {
+            level
+                .chunks(2)
+                .map(|pair| parent_hash(algorithm, pair))
+                .collect()
+        })
+    }
+
+    /// Builds the tree over `items`, hashing each one with `leaf_hash()`
+    pub fn from_items<T: Serialize>(
+        algorithm: HashAlgorithm,
+        items: &[T],
+    ) -> Result<Self, CustomErrs> {
+        let leaves = items
+            .iter()
+            .map(|item| leaf_hash(algorithm, item))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(algorithm, leaves))
+    }
+
+    /// Same tree as `from_items()`, with the hashes of each level spread over the threads of
+    /// the current rayon pool
+    pub fn from_items_parallel<T: Serialize + Sync>(
+        algorithm: HashAlgorithm,
+        items: &[T],
+    ) -> Result<Self, CustomErrs> {
+        let leaves = items
+            .par_iter()
+            .map(|item| leaf_hash(algorithm, item))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::grow(algorithm, leaves, |level| {
+            level
+                .par_chunks(2)
+                .map(|pair| parent_hash(algorithm, pair))
+                .collect()
+        }))
+    }
+
+    /// Stacks levels computed by `next_level` on top of `leaves` until one node is left
+    fn grow(
+        algorithm: HashAlgorithm,
+        leaves: Vec<Hash>,
+        next_level: impl Fn(&[Hash]) -> Vec<Hash>,
+    ) -> Self {
+        let mut levels = vec![leaves];
+
+        while levels.last().unwrap().len() > 1 {
+            let next = next_level(levels.last().unwrap());
+            levels.push(next);
+        }
+
+        Self { algorithm, levels }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Root of the tree. The root of an empty tree is the hash of no bytes.
+    pub fn root(&self) -> Hash {
+        match self.levels.last().unwrap().first() {
+            Some(root) => *root,
+            None => self.algorithm.digest(&[]),
+        }
+    }
+
+    /// Returns the proof that the leaf at `index` is part of this tree
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut siblings = vec![];
+        let mut position = index;
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < position {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                siblings.push((side, level[sibling]));
+            }
+            position /= 2;
+        }
+
+        Some(MerkleProof {
+            algorithm: self.algorithm,
+            index,
+            leaf_count: self.leaf_count(),
+            siblings,
+        })
+    }
+}
+
+/// Compact evidence that one leaf belongs to a tree with a known root.
+///
+/// Holds one sibling hash per level of the tree, so its size grows with the
+/// logarithm of the number of leaves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Algorithm the tree the proof was taken from is hashed with
+    pub algorithm: HashAlgorithm,
+    /// Position of the proven leaf
+    pub index: usize,
+    /// Number of leaves in the tree the proof was taken from
+    pub leaf_count: usize,
+    /// Sibling hashes from the leaf level up to just below the root
+    pub siblings: Vec<(Side, Hash)>,
+}
+
+impl MerkleProof {
+    /// Recomputes the root from `leaf` and checks it against `root`.
+    ///
+    /// The path is the one `index` takes in a tree of `leaf_count` leaves: a proof whose
+    /// sides or number of siblings do not match that path is rejected, so a valid proof
+    /// also vouches for the position of the leaf.
+    pub fn verify(&self, root: &Hash, leaf: &Hash) -> bool {
+        if self.index >= self.leaf_count {
+            return false;
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut computed = *leaf;
+        let mut position = self.index;
+        let mut width = self.leaf_count;
+
+        while width > 1 {
+            let sibling = position ^ 1;
+            if sibling < width {
+                let expected = if sibling < position {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                computed = match siblings.next() {
+                    Some((Side::Left, hash)) if expected == Side::Left => {
+                        node_hash(self.algorithm, hash, &computed)
+                    }
+                    Some((Side::Right, hash)) if expected == Side::Right => {
+                        node_hash(self.algorithm, &computed, hash)
+                    }
+                    _ => return false,
+                };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && computed == *root
+    }
+}
+
+/// Checks that `item` is the leaf proven by `proof` in the tree with the given root
+pub fn verify_proof<T: Serialize>(root: &Hash, item: &T, proof: &MerkleProof) -> bool {
+    leaf_hash(proof.algorithm, item).is_ok_and(|leaf| proof.verify(root, &leaf))
+}
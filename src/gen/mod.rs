@@ -1,96 +1,202 @@
-use std::{fmt::Debug, ops::Deref};
-
-use serde::Serialize;
-use sha2::{
-    digest::{
-        generic_array::GenericArray,
-        typenum::{UInt, UTerm, B0, B1},
-    },
-    Digest, Sha256,
-};
+mod hasher;
+pub mod hd;
+mod keys;
+pub mod merkle;
+
+use std::{fmt, ops::Deref, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::errs::CustomErrs;
 
-type Hxsh = GenericArray<u8, UInt<UInt<UInt<UInt<UInt<UInt<UTerm, B1>, B0>, B0>, B0>, B0>, B0>>;
+pub use hasher::{Blake3, HashAlgorithm, Hasher, Sha256, Sha3_256, Sha512_256, HASH_LEN};
+pub use keys::{
+    verify_batch, KeyPair, PrivateKey, PublicKey, Signature, PRIVATE_KEY_LEN, PUBLIC_KEY_LEN,
+    SIGNATURE_LEN,
+};
 
-#[derive(PartialEq, Eq)]
+/// Digest computed by a `Hasher`, tagged with the algorithm that produced it.
+///
+/// The tag is part of the hash: two hashes are only equal if they hold the same bytes
+/// computed with the same algorithm, as their text forms are. It tells `validate()` how to
+/// recompute the hash, and is kept when the hash is serialized.
+///
+/// Its text form, used by `Display`, `FromStr` and human-readable serde formats such as
+/// JSON, is lowercase hex, prefixed with the `HashAlgorithm::name()` and a colon unless
+/// the algorithm is the default SHA-256. Binary formats such as bincode hold the algorithm
+/// followed by the raw bytes.
+///
+/// # Example
+/// ```
+/// use blockchain::gen::{self, Hash, HashAlgorithm};
+///
+/// let hash = gen::digest(b"abc");
+/// assert_eq!(
+///     hash.to_string(),
+///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+/// );
+/// assert_eq!(hash.to_string().parse::<Hash>().unwrap(), hash);
+///
+/// let hash = HashAlgorithm::Blake3.digest(b"abc");
+/// assert!(hash.to_string().starts_with("blake3:"));
+/// let parsed: Hash = hash.to_string().parse().unwrap();
+/// assert_eq!(parsed, hash);
+/// assert_ne!(Hash::from(hash.to_bytes()), hash);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Hash {
-    data: Hxsh,
+    algorithm: HashAlgorithm,
+    data: [u8; HASH_LEN],
+}
+
+impl Hash {
+    pub fn new(algorithm: HashAlgorithm, data: [u8; HASH_LEN]) -> Self {
+        Self { algorithm, data }
+    }
+
+    /// Rebuilds a `Hash` from its raw bytes, e.g. the `hash` of a `MerkleProof`
+    pub fn from_slice(algorithm: HashAlgorithm, bytes: &[u8]) -> Result<Self, CustomErrs> {
+        let data = bytes
+            .try_into()
+            .map_err(|_| CustomErrs::InvalidHash { len: bytes.len() })?;
+        Ok(Self { algorithm, data })
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// The same bytes, tagged as computed with `algorithm`
+    pub fn with_algorithm(self, algorithm: HashAlgorithm) -> Self {
+        Self { algorithm, ..self }
+    }
+
+    pub fn to_bytes(self) -> [u8; HASH_LEN] {
+        self.data
+    }
 }
 
-impl Debug for Hash {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.as_slice().fmt(f)
+impl From<[u8; HASH_LEN]> for Hash {
+    fn from(data: [u8; HASH_LEN]) -> Self {
+        Self::new(HashAlgorithm::default(), data)
     }
 }
 
 impl Deref for Hash {
-    type Target = Hxsh;
+    type Target = [u8];
     fn deref(&self) -> &Self::Target {
         &self.data
     }
 }
 
-pub fn encrypt<T: Sized + Serialize>(data: &T) -> Hash {
-    let bytes = bincode::serialize(data).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(bytes);
-    let data: Hxsh = hasher.finalize();
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.algorithm != HashAlgorithm::default() {
+            write!(f, "{}:", self.algorithm.name())?;
+        }
+        f.write_str(&hex::encode(self.data))
+    }
+}
 
-    Hash { data }
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({:?}, {})", self.algorithm, self)
+    }
 }
 
-pub fn validate<T: Sized + Serialize>(obj: &T, hash: Hash) -> bool {
-    hash.data == encrypt(obj).data
+impl FromStr for Hash {
+    type Err = CustomErrs;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (algorithm, digits) = match text.split_once(':') {
+            Some((name, digits)) => match HashAlgorithm::from_name(name) {
+                Some(algorithm) => (algorithm, digits),
+                None => {
+                    return Err(CustomErrs::UnknownHashAlgorithm {
+                        name: name.to_owned(),
+                    })
+                }
+            },
+            None => (HashAlgorithm::default(), text),
+        };
+
+        let mut data = [0; HASH_LEN];
+        hex::decode_to_slice(digits, &mut data)
+            .map_err(|err| CustomErrs::CouldNotParseHash { source: err.into() })?;
+        Ok(Self { algorithm, data })
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            (self.algorithm, self.data).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(de::Error::custom)
+        } else {
+            <(HashAlgorithm, [u8; HASH_LEN])>::deserialize(deserializer)
+                .map(|(algorithm, data)| Self { algorithm, data })
+        }
+    }
 }
 
-use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+/// Hashes the bincode encoding of `data` with SHA-256.
+///
+/// Chains hash with the algorithm of their `Genesis` instead, see `HashAlgorithm::encrypt()`.
+pub fn encrypt<T: Sized + Serialize>(data: &T) -> Result<Hash, CustomErrs> {
+    HashAlgorithm::Sha256.encrypt(data)
+}
+
+/// Hashes raw bytes as they are with SHA-256, without serializing them first
+pub fn digest(bytes: &[u8]) -> Hash {
+    Sha256::hash(bytes)
+}
+
+/// `true` if `hash` is the hash of `obj` under the algorithm `hash` was computed with
+pub fn validate<T: Sized + Serialize>(obj: &T, hash: Hash) -> bool {
+    hash.algorithm
+        .encrypt(obj)
+        .is_ok_and(|computed| computed == hash)
+}
 
 ///
-///  Generates ed25519 key pairs in the form (public_key, private_key)
+///  Generates an ed25519 key pair
 ///  # Example
 /// ```
+/// use blockchain::gen;
 ///
-/// let (public_key, private_key): (Vec<u8>, Vec<u8>) = gen::generate_key_pair();
+/// let key_pair = gen::generate_key_pair();
+/// let signature = gen::sign(b"message", key_pair.private_key());
+/// assert!(gen::verify_signature(key_pair.public_key(), b"message", &signature).is_ok());
 /// ```
-///
+pub fn generate_key_pair() -> KeyPair {
+    KeyPair::generate()
+}
 
-pub fn generate_key_pair() -> (Vec<u8>, Vec<u8>) {
-    // Generate a new key pair
-    let mut c = rand::rngs::OsRng;
-    let keypair = Keypair::generate(&mut c);
-
-    // Serialize the private and public keys as byte vectors
-    let private_key = keypair.secret.to_bytes().to_vec();
-    let public_key = keypair.public.to_bytes().to_vec();
-
-    // Returns the public and private keys as byte vectors
-    (public_key, private_key)
-}
-
-pub fn sign(msg: &[u8], key: &[u8]) -> Result<Vec<u8>, CustomErrs> {
-    // Parse the private key
-    match SecretKey::from_bytes(key) {
-        Ok(secret) => {
-            // Create a signature for the message
-            let keypair = Keypair {
-                public: PublicKey::from(&secret),
-                secret,
-            };
-            let signature = keypair.sign(msg);
-            Ok(signature.as_ref().to_vec())
-        }
-        Err(_) => Err(CustomErrs::InvalidPrivateKey),
-    }
+pub fn sign(msg: &[u8], key: &PrivateKey) -> Signature {
+    key.sign(msg)
 }
 
-pub fn verify_signature(public_key: &[u8], msg: &[u8], signature: &[u8]) -> Result<(), CustomErrs> {
-    let dalek = Signature::from_bytes(signature).map_err(|_| CustomErrs::InvalidSignature)?;
-    match PublicKey::from_bytes(public_key) {
-        Ok(key) => match key.verify(msg, &dalek) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(CustomErrs::VerificationDoesNotMatch),
-        },
-        Err(_) => Err(CustomErrs::InvalidPublicKey),
-    }
+pub fn verify_signature(
+    public_key: &PublicKey,
+    msg: &[u8],
+    signature: &Signature,
+) -> Result<(), CustomErrs> {
+    public_key.verify(msg, signature)
 }
@@ -1,20 +1,29 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     blockchain::{Block, PublishedBlock, Record, SignedRecord},
     errs::CustomErrs,
 };
 
-#[derive(Serialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub struct QueryRange {
     pub begin: i64,
     pub end: i64,
 }
 
 impl QueryRange {
-    fn new(begin: i64, end: i64) -> Self {
+    pub fn new(begin: i64, end: i64) -> Self {
         Self { begin, end }
     }
+
+    /// Number of rows covered by this range
+    pub fn len(&self) -> i64 {
+        (self.end - self.begin + 1).max(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub trait Database<T>
@@ -23,32 +32,23 @@ where
 {
     fn establish_connection(&self) -> Result<(), CustomErrs>;
     fn insert_block(&self, block: &Block<T>) -> Result<QueryRange, CustomErrs> {
-        let begin = self.next_stamp();
+        let begin = self.next_stamp()?;
 
         let end = begin + block.size() - 1;
 
         let block_position = QueryRange::new(begin, end);
 
-        let mut count = begin;
-
-        for record in block.signed_records.iter() {
+        for (count, record) in (begin..).zip(block.signed_records.iter()) {
             self.insert_row(record, count)?;
-            count += 1;
         }
 
         Ok(block_position)
     }
     fn insert_row(&self, record: &SignedRecord<T>, stamp: i64) -> Result<(), CustomErrs>;
     fn insert_hash(&self, published_block: PublishedBlock) -> Result<(), CustomErrs>;
-    fn next_stamp(&self) -> i64;
+    fn next_stamp(&self) -> Result<i64, CustomErrs>;
 }
 
-///
-///
-///
-///
-///
-///
 use std::collections::HashSet;
 
 pub trait DatabaseInsertable {
@@ -56,7 +56,16 @@ pub trait DatabaseInsertable {
 
     fn columns() -> &'static [&'static str];
 
+    /// Column uniquely identifying a row, which backends may index for `find_rows()`
+    fn key_column() -> Option<&'static str> {
+        None
+    }
+
     fn len(&self) -> i64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub trait Row<T> {
@@ -73,16 +82,16 @@ pub trait Database2 {
 
     /// Returns `None` if the table is not present in the database
     /// and `Some(number of rows)` if the table exists
-    fn size_of_table<T: DatabaseInsertable>(&self) -> Option<i64> {
+    fn size_of_table<T: DatabaseInsertable>(&self) -> Result<Option<i64>, CustomErrs> {
         if self.table_exists::<T>() {
-            Some(self.len(&T::get_name()))
+            self.len(T::get_name()).map(Some)
         } else {
-            None
+            Ok(None)
         }
     }
 
-    /// Do not use, may fail
-    fn len(&self, table_name: &str) -> i64;
+    /// Number of rows in the table named `table_name`
+    fn len(&self, table_name: &str) -> Result<i64, CustomErrs>;
 
     /// Checks if the given type has a table in the database
     fn table_exists<T: DatabaseInsertable>(&self) -> bool {
@@ -106,7 +115,9 @@ pub trait Database2 {
     fn get_tables(&self) -> &HashSet<String>;
 
     /// Inserts the given Item into its table in the database
-    fn insert<T: DatabaseInsertable + Copy + IntoIterator<Item = Vec<String>>>(
+    fn insert<
+        T: DatabaseInsertable + Copy + IntoIterator<Item = Result<Vec<String>, CustomErrs>>,
+    >(
         &mut self,
         item: &T,
     ) -> Result<QueryRange, CustomErrs> {
@@ -120,22 +131,77 @@ pub trait Database2 {
     /// Use with caution. Recommended `insert()`
     ///
     /// This inserts item into the table for T given that the table for T exists in the database
-    fn insert_table_exists<T: DatabaseInsertable + Copy + IntoIterator<Item = Vec<String>>>(
+    fn insert_table_exists<
+        T: DatabaseInsertable + Copy + IntoIterator<Item = Result<Vec<String>, CustomErrs>>,
+    >(
         &self,
         item: &T,
     ) -> Result<QueryRange, CustomErrs> {
-        let begin = self.len(T::get_name());
-        let end = begin + item.len() - 1;
+        let begin = self.len(T::get_name())?;
+        let mut end = begin - 1;
 
-        let mut iter_vec = item.into_iter();
-
-        for _ in begin..=end {
-            let vec = iter_vec.next().unwrap();
-            self.insert_row::<T>(&vec)?
+        for row in item.into_iter() {
+            self.insert_row::<T>(&row?)?;
+            end += 1;
         }
 
         Ok(QueryRange { begin, end })
     }
     /// Inserts an insertable object into the database
     fn insert_row<T: DatabaseInsertable>(&self, columns: &[String]) -> Result<(), CustomErrs>;
+
+    /// Returns the rows of T's table that fall within `range`, in insertion order.
+    ///
+    /// Positions are zero-based, matching the `QueryRange` returned by `insert()`.
+    /// Each row holds one value per entry of `T::columns()`.
+    fn get_rows<T: DatabaseInsertable>(
+        &self,
+        range: QueryRange,
+    ) -> Result<Vec<Vec<String>>, CustomErrs>;
+
+    /// Returns the single row of T's table at `position`
+    fn get_row<T: DatabaseInsertable>(&self, position: i64) -> Result<Vec<String>, CustomErrs> {
+        self.get_rows::<T>(QueryRange::new(position, position))?
+            .pop()
+            .ok_or_else(|| CustomErrs::NoSuchRowInTable {
+                table: T::get_name().to_owned(),
+                position,
+            })
+    }
+
+    /// Returns every row of T's table whose `column` equals `value`, in insertion order
+    fn find_rows<T: DatabaseInsertable>(
+        &self,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<Vec<String>>, CustomErrs>;
+
+    /// Starts a transaction. Until `commit()`, no write is visible to other connections
+    /// and every write can be undone with `rollback()`. Transactions do not nest.
+    fn begin(&mut self) -> Result<(), CustomErrs>;
+
+    /// Makes every write since `begin()` permanent
+    fn commit(&mut self) -> Result<(), CustomErrs>;
+
+    /// Undoes every write since `begin()`
+    fn rollback(&mut self) -> Result<(), CustomErrs>;
+
+    /// Runs `f` inside a transaction, committing if it succeeds and rolling back if it fails
+    fn transaction<V, F>(&mut self, f: F) -> Result<V, CustomErrs>
+    where
+        Self: Sized,
+        F: FnOnce(&mut Self) -> Result<V, CustomErrs>,
+    {
+        self.begin()?;
+        match f(self) {
+            Ok(value) => {
+                self.commit()?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.rollback()?;
+                Err(err)
+            }
+        }
+    }
 }
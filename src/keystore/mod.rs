@@ -0,0 +1,469 @@
+//! Passphrase-protected storage for key pairs.
+//!
+//! Every key pair lives in its own file, `<label>.key`, in the keystore directory.
+//!
+//! # File format, version 1
+//!
+//! A key file is a JSON object. Byte strings are lowercase hex.
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "label": "alice",
+//!   "public_key": "<32 bytes>",
+//!   "kdf": {
+//!     "algorithm": "argon2id",
+//!     "memory_kib": 65536,
+//!     "iterations": 3,
+//!     "parallelism": 1,
+//!     "salt": "<16 bytes>"
+//!   },
+//!   "cipher": {
+//!     "algorithm": "xchacha20poly1305",
+//!     "nonce": "<24 bytes>"
+//!   },
+//!   "ciphertext": "<48 bytes>"
+//! }
+//! ```
+//!
+//! The 32 byte encryption key is derived from the passphrase and `salt` with Argon2id
+//! (version 0x13) and the stored parameters. `ciphertext` is the private key encrypted
+//! with XChaCha20-Poly1305 under `nonce`, followed by the 16 byte tag. The associated
+//! data is the version byte, then the bytes of `label`, then the 32 bytes of
+//! `public_key`, so a file whose label or public key was edited, or that was renamed,
+//! no longer decrypts.
+//!
+//! Every save draws a fresh salt and nonce. Files are written to a temporary file of
+//! their own, then linked to their final name, or renamed over the old one when the
+//! passphrase changes, so a crash never leaves a half-written key file behind and two
+//! saves under the same label never replace one another.
+
+use std::{
+    array::TryFromSliceError,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    errs::{CustomErrs, Source},
+    gen::{KeyPair, PrivateKey, PublicKey},
+};
+
+/// Version of the key files written by this module
+pub const KEY_FILE_VERSION: u8 = 1;
+
+const KEY_FILE_EXTENSION: &str = "key";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// Cost of deriving the encryption key from a passphrase with Argon2id.
+///
+/// The parameters are stored in every key file, so changing them only affects files
+/// saved afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// 64 MiB, 3 passes, 1 lane
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+/// A key pair stored in a `KeyStore`, as listed without its passphrase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub label: String,
+    pub public_key: PublicKey,
+}
+
+/// Directory of key files, each encrypted with its own passphrase.
+///
+/// # Example
+/// ```
+/// use blockchain::{gen, keystore::{KdfParams, KeyStore}};
+///
+/// let dir = std::env::temp_dir().join(format!("keystore-doc-{}", std::process::id()));
+/// // Cheap parameters keep the example fast; use the defaults for real keys
+/// let store = KeyStore::open(&dir)
+///     .unwrap()
+///     .with_kdf_params(KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 });
+///
+/// let key_pair = gen::generate_key_pair();
+/// store.save("alice", &key_pair, "correct horse").unwrap();
+///
+/// let loaded = store.load("alice", "correct horse").unwrap();
+/// assert_eq!(loaded.public_key(), key_pair.public_key());
+/// assert!(store.load("alice", "battery staple").is_err());
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    dir: PathBuf,
+    kdf_params: KdfParams,
+}
+
+impl KeyStore {
+    /// Opens the keystore in `dir`, creating the directory if needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, CustomErrs> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|err| io_error(&dir, err))?;
+        Ok(Self {
+            dir,
+            kdf_params: KdfParams::default(),
+        })
+    }
+
+    /// Derives the encryption keys of files saved from now on with `kdf_params`
+    pub fn with_kdf_params(mut self, kdf_params: KdfParams) -> Self {
+        self.kdf_params = kdf_params;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Encrypts `key_pair` with `passphrase` and stores it under `label`.
+    ///
+    /// Fails if a key is already stored under `label`, even if it was saved concurrently.
+    pub fn save(
+        &self,
+        label: &str,
+        key_pair: &KeyPair,
+        passphrase: &str,
+    ) -> Result<(), CustomErrs> {
+        let path = self.path(label)?;
+        let already_exists = || CustomErrs::KeyAlreadyExists {
+            label: label.to_owned(),
+        };
+        // Spares deriving the encryption key in the common case
+        if path.exists() {
+            return Err(already_exists());
+        }
+
+        let key_file = KeyFile::seal(label, key_pair, passphrase, self.kdf_params)?;
+        let temporary = self.write_temporary(&path, &key_file)?;
+        let linked = fs::hard_link(&temporary, &path);
+        let _ = fs::remove_file(&temporary);
+
+        match linked {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Err(already_exists()),
+            Err(err) => Err(io_error(&path, err)),
+        }
+    }
+
+    /// Decrypts the key pair stored under `label`
+    pub fn load(&self, label: &str, passphrase: &str) -> Result<KeyPair, CustomErrs> {
+        let path = self.path(label)?;
+        self.read(&path, label)?.open(label, passphrase, &path)
+    }
+
+    /// Lists the stored key pairs, sorted by label.
+    ///
+    /// Key files that cannot be read or decoded are left out, `load()` tells what is wrong
+    /// with them.
+    pub fn list(&self) -> Result<Vec<Identity>, CustomErrs> {
+        let mut identities = vec![];
+
+        for entry in fs::read_dir(&self.dir).map_err(|err| io_error(&self.dir, err))? {
+            let path = entry.map_err(|err| io_error(&self.dir, err))?.path();
+            let label = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(label)
+                    if path.extension() == Some(KEY_FILE_EXTENSION.as_ref())
+                        && is_valid_label(label) =>
+                {
+                    label.to_owned()
+                }
+                _ => continue,
+            };
+
+            let public_key = self
+                .read(&path, &label)
+                .and_then(|key_file| key_file.public_key(&path));
+            if let Ok(public_key) = public_key {
+                identities.push(Identity { public_key, label });
+            }
+        }
+
+        identities.sort_by(|a, b| a.label.cmp(&b.label));
+        Ok(identities)
+    }
+
+    /// Re-encrypts the key pair stored under `label` with `new_passphrase`
+    pub fn change_passphrase(
+        &self,
+        label: &str,
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), CustomErrs> {
+        let key_pair = self.load(label, old_passphrase)?;
+        let path = self.path(label)?;
+        let key_file = KeyFile::seal(label, &key_pair, new_passphrase, self.kdf_params)?;
+        let temporary = self.write_temporary(&path, &key_file)?;
+
+        fs::rename(&temporary, &path).map_err(|err| {
+            let _ = fs::remove_file(&temporary);
+            io_error(&path, err)
+        })
+    }
+
+    fn path(&self, label: &str) -> Result<PathBuf, CustomErrs> {
+        if !is_valid_label(label) {
+            return Err(CustomErrs::InvalidKeyLabel {
+                label: label.to_owned(),
+            });
+        }
+        Ok(self.dir.join(format!("{}.{}", label, KEY_FILE_EXTENSION)))
+    }
+
+    fn read(&self, path: &Path, label: &str) -> Result<KeyFile, CustomErrs> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(CustomErrs::NoSuchKey {
+                    label: label.to_owned(),
+                })
+            }
+            Err(err) => return Err(io_error(path, err)),
+        };
+
+        let versioned: Versioned =
+            serde_json::from_str(&json).map_err(|err| corrupted(path, err.into()))?;
+        if versioned.version != KEY_FILE_VERSION {
+            return Err(CustomErrs::UnsupportedKeyFileVersion {
+                found: versioned.version,
+            });
+        }
+        serde_json::from_str(&json).map_err(|err| corrupted(path, err.into()))
+    }
+
+    /// Writes `key_file` to a new temporary file next to `path`, named uniquely so that
+    /// concurrent writes never share one, and returns its path
+    fn write_temporary(&self, path: &Path, key_file: &KeyFile) -> Result<PathBuf, CustomErrs> {
+        let json = serde_json::to_string_pretty(key_file)
+            .map_err(|err| CustomErrs::CouldNotSerialize { source: err.into() })?;
+        let temporary = path.with_extension(format!(
+            "{}-{:016x}.tmp",
+            std::process::id(),
+            rand::thread_rng().next_u64()
+        ));
+
+        let mut file = fs::OpenOptions::new();
+        file.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+        let mut file = file.open(&temporary).map_err(|err| io_error(path, err))?;
+        file.write_all(json.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|err| {
+                let _ = fs::remove_file(&temporary);
+                io_error(path, err)
+            })?;
+        Ok(temporary)
+    }
+}
+
+/// The part of a key file every version shares
+#[derive(Deserialize)]
+struct Versioned {
+    version: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    version: u8,
+    label: String,
+    public_key: String,
+    kdf: KdfSection,
+    cipher: CipherSection,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KdfSection {
+    algorithm: KdfAlgorithm,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherSection {
+    algorithm: CipherAlgorithm,
+    nonce: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KdfAlgorithm {
+    Argon2id,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CipherAlgorithm {
+    XChaCha20Poly1305,
+}
+
+impl KeyFile {
+    fn seal(
+        label: &str,
+        key_pair: &KeyPair,
+        passphrase: &str,
+        kdf_params: KdfParams,
+    ) -> Result<Self, CustomErrs> {
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, kdf_params)?;
+        let aad = associated_data(label, key_pair.public_key());
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: key_pair.private_key().as_bytes(),
+                    aad: &aad,
+                },
+            )
+            // Encryption only fails for messages far longer than a private key
+            .unwrap();
+
+        Ok(Self {
+            version: KEY_FILE_VERSION,
+            label: label.to_owned(),
+            public_key: key_pair.public_key().to_string(),
+            kdf: KdfSection {
+                algorithm: KdfAlgorithm::Argon2id,
+                memory_kib: kdf_params.memory_kib,
+                iterations: kdf_params.iterations,
+                parallelism: kdf_params.parallelism,
+                salt: hex::encode(salt),
+            },
+            cipher: CipherSection {
+                algorithm: CipherAlgorithm::XChaCha20Poly1305,
+                nonce: hex::encode(nonce),
+            },
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Decrypts the private key, which must have been saved under `label`
+    fn open(&self, label: &str, passphrase: &str, path: &Path) -> Result<KeyPair, CustomErrs> {
+        let public_key = self.public_key(path)?;
+        let salt = hex::decode(&self.kdf.salt).map_err(|err| corrupted(path, err.into()))?;
+        let nonce = hex::decode(&self.cipher.nonce).map_err(|err| corrupted(path, err.into()))?;
+        let nonce: [u8; NONCE_LEN] = nonce
+            .as_slice()
+            .try_into()
+            .map_err(|err: TryFromSliceError| corrupted(path, err.into()))?;
+        let ciphertext =
+            hex::decode(&self.ciphertext).map_err(|err| corrupted(path, err.into()))?;
+
+        let kdf_params = KdfParams {
+            memory_kib: self.kdf.memory_kib,
+            iterations: self.kdf.iterations,
+            parallelism: self.kdf.parallelism,
+        };
+        let key = derive_key(passphrase, &salt, kdf_params)?;
+        let aad = associated_data(label, &public_key);
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| CustomErrs::WrongPassphrase)?,
+        );
+
+        let key_pair = KeyPair::from_private_key(PrivateKey::from_bytes(&plaintext)?);
+        if *key_pair.public_key() != public_key {
+            return Err(CustomErrs::KeyMismatch);
+        }
+        Ok(key_pair)
+    }
+
+    fn public_key(&self, path: &Path) -> Result<PublicKey, CustomErrs> {
+        self.public_key
+            .parse()
+            .map_err(|err: CustomErrs| corrupted(path, err.into()))
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf_params: KdfParams,
+) -> Result<Zeroizing<[u8; KEY_LEN]>, CustomErrs> {
+    let params = Params::new(
+        kdf_params.memory_kib,
+        kdf_params.iterations,
+        kdf_params.parallelism,
+        Some(KEY_LEN),
+    )
+    .map_err(|err| CustomErrs::KeyDerivationFailed { source: err.into() })?;
+
+    let mut key = Zeroizing::new([0; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|err| CustomErrs::KeyDerivationFailed { source: err.into() })?;
+    Ok(key)
+}
+
+fn associated_data(label: &str, public_key: &PublicKey) -> Vec<u8> {
+    let mut aad = vec![KEY_FILE_VERSION];
+    aad.extend_from_slice(label.as_bytes());
+    aad.extend_from_slice(public_key.as_bytes());
+    aad
+}
+
+/// Labels name files, so they are limited to ASCII letters, digits, `-`, `_` and `.`,
+/// and may not start with a `.`
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && !label.starts_with('.')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> CustomErrs {
+    CustomErrs::CouldNotAccessKeyStore {
+        path: path.display().to_string(),
+        source: err.into(),
+    }
+}
+
+fn corrupted(path: &Path, source: Source) -> CustomErrs {
+    CustomErrs::CorruptedKeyFile {
+        path: path.display().to_string(),
+        source,
+    }
+}
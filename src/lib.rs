@@ -1,6 +1,10 @@
 pub mod blockchain;
+pub mod codec;
+pub mod consensus;
 pub mod errs;
 pub mod gen;
 pub mod io;
+pub mod keystore;
+pub mod mempool;
+pub mod node;
 pub mod utils;
-pub mod node;
\ No newline at end of file
@@ -1,26 +1,32 @@
 use blockchain::{
     block,
-    blockchain::{Block, BlockChain, Record, SignedRecord},
+    blockchain::{Block, BlockChain, Genesis, Record, SignedRecord},
     gen,
-    utils::{SqliteDB2, Transaction},
+    utils::{InMemoryDB, Transaction},
 };
 
 fn main() {
-    // (public key, private key)
-    let (public_key, private_key) = gen::generate_key_pair();
+    let key_pair = gen::generate_key_pair();
+
+    let genesis = Genesis::new("blockchain");
+    let chain_id = genesis.chain_id();
 
     let trans1: Transaction = Transaction::new("A", "B", "2");
     let trans2: Transaction = Transaction::new("B", "A", "5");
 
-    let signed_trans1: SignedRecord<Transaction> = trans1.sign(&private_key, &public_key).unwrap();
-    let signed_trans2: SignedRecord<Transaction> = trans2.sign(&private_key, &public_key).unwrap();
+    let signed_trans1: SignedRecord<Transaction> =
+        trans1.sign(key_pair.private_key(), &chain_id, 0).unwrap();
+    let signed_trans2: SignedRecord<Transaction> =
+        trans2.sign(key_pair.private_key(), &chain_id, 1).unwrap();
 
     let block: Block<Transaction> = block![signed_trans1, signed_trans2];
 
-    let mut blockchain: BlockChain<SqliteDB2> = BlockChain::open(SqliteDB2::new(r"db\database.db"));
+    // Swap in `SqliteDB2::new(path)` or `LogDB::open(dir)` to keep the chain on disk
+    let mut blockchain: BlockChain<InMemoryDB> =
+        BlockChain::open(InMemoryDB::new(), genesis).unwrap();
 
     match blockchain.push(&block) {
         Ok(feedback) => println!("Success! {:?}", feedback),
-        Err(err) => println!("Failure! {:?}", err),
+        Err(err) => println!("Failure! {}", err),
     }
 }
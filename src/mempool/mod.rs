@@ -0,0 +1,302 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::{
+    blockchain::{Block, BlockChain, Record, SignedRecord, Signer},
+    consensus::Consensus,
+    errs::CustomErrs,
+    gen::Hash,
+    io::Database2,
+};
+
+/// Decides which unconfirmed records go into the next block first.
+///
+/// Higher scores are picked first and evicted last. Records with equal scores are
+/// ordered by arrival. Any `Fn(&SignedRecord<R>) -> i64` closure is a `Scorer`.
+pub trait Scorer<R: Record> {
+    fn score(&self, record: &SignedRecord<R>) -> i64;
+}
+
+impl<R: Record, F: Fn(&SignedRecord<R>) -> i64> Scorer<R> for F {
+    fn score(&self, record: &SignedRecord<R>) -> i64 {
+        self(record)
+    }
+}
+
+/// Gives every record the same score, making the pool first-in first-out
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FifoScorer;
+
+impl<R: Record> Scorer<R> for FifoScorer {
+    fn score(&self, _: &SignedRecord<R>) -> i64 {
+        0
+    }
+}
+
+/// Position of an entry in the pool: best score first, then oldest first
+type Priority = (Reverse<i64>, u64);
+
+/// Position of an entry among the records of its signer: lowest nonce first
+type SignerSlot = (u64, Priority);
+
+/// Comes before the priority of every entry
+const FIRST_PRIORITY: Priority = (Reverse(i64::MAX), 0);
+
+struct Entry<R: Record> {
+    record: SignedRecord<R>,
+    priority: Priority,
+}
+
+/// Set of signed records waiting to be placed in a block.
+///
+/// Records are verified on admission and identified by `SignedRecord::hash()`, so the
+/// same record is never held twice. When the pool is full, a new record evicts the
+/// lowest priority one if it scores higher, and is rejected otherwise.
+pub struct MemPool<R: Record, S: Scorer<R> = FifoScorer> {
+    entries: HashMap<Hash, Entry<R>>,
+    order: BTreeMap<Priority, Hash>,
+    by_signer: HashMap<Signer, BTreeMap<SignerSlot, Hash>>,
+    capacity: usize,
+    scorer: S,
+    arrivals: u64,
+}
+
+impl<R: Record> MemPool<R> {
+    /// Creates a first-in first-out pool holding at most `capacity` records
+    pub fn new(capacity: usize) -> Self {
+        Self::with_scorer(capacity, FifoScorer)
+    }
+}
+
+impl<R: Record, S: Scorer<R>> MemPool<R, S> {
+    /// Creates a pool holding at most `capacity` records, ordered by `scorer`
+    pub fn with_scorer(capacity: usize, scorer: S) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            by_signer: HashMap::new(),
+            capacity,
+            scorer,
+            arrivals: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &Hash) -> Option<&SignedRecord<R>> {
+        self.entries.get(hash).map(|entry| &entry.record)
+    }
+
+    /// Verifies and validates `record`, see `Record::validate()`, and adds it to the pool.
+    ///
+    /// The record must be signed for `chain` with a nonce `chain` has not used yet for its
+    /// signer, see `BlockChain::next_nonce()`; records with later nonces wait in the pool
+    /// for the ones before them. Returns the evicted record, if one had to make room.
+    pub fn admit<D: Database2, C: Consensus>(
+        &mut self,
+        record: SignedRecord<R>,
+        chain: &BlockChain<D, C>,
+    ) -> Result<Option<SignedRecord<R>>, CustomErrs> {
+        Self::check(&record, chain)?;
+        self.insert(record, chain)
+    }
+
+    /// Same as `admit()`, also checking `record` against `chain` with
+    /// `Record::validate_against()`
+    pub fn admit_against<D: Database2, C: Consensus>(
+        &mut self,
+        record: SignedRecord<R>,
+        chain: &BlockChain<D, C>,
+    ) -> Result<Option<SignedRecord<R>>, CustomErrs> {
+        Self::check(&record, chain)?;
+        record
+            .get_record()
+            .validate_against(record.get_signer(), chain, &[])?;
+        self.insert(record, chain)
+    }
+
+    fn check<D: Database2, C: Consensus>(
+        record: &SignedRecord<R>,
+        chain: &BlockChain<D, C>,
+    ) -> Result<(), CustomErrs> {
+        let next = chain.next_nonce(record.get_signer());
+        if record.nonce < next {
+            return Err(CustomErrs::ReplayedNonce {
+                expected: next,
+                found: record.nonce,
+            });
+        }
+        record.verify_for_chain(&chain.chain_id())?;
+        record.get_record().validate()
+    }
+
+    /// Adds a checked `record`, first dropping the records of its signer that `chain` has
+    /// made stale since they were admitted
+    fn insert<D: Database2, C: Consensus>(
+        &mut self,
+        record: SignedRecord<R>,
+        chain: &BlockChain<D, C>,
+    ) -> Result<Option<SignedRecord<R>>, CustomErrs> {
+        let hash = record.hash()?;
+        if self.entries.contains_key(&hash) {
+            return Err(CustomErrs::DuplicateRecord);
+        }
+        self.purge(&record.signer, chain.next_nonce(&record.signer));
+
+        let priority = (Reverse(self.scorer.score(&record)), self.arrivals);
+
+        let evicted = if self.entries.len() >= self.capacity {
+            match self.order.last_key_value() {
+                Some((worst, _)) if priority < *worst => self.pop_worst(),
+                _ => return Err(CustomErrs::MemPoolFull),
+            }
+        } else {
+            None
+        };
+
+        self.arrivals += 1;
+        self.order.insert(priority, hash);
+        self.by_signer
+            .entry(record.signer.clone())
+            .or_default()
+            .insert((record.nonce, priority), hash);
+        self.entries.insert(hash, Entry { record, priority });
+
+        Ok(evicted)
+    }
+
+    /// Removes the record with the given hash, if present
+    pub fn remove(&mut self, hash: &Hash) -> Option<SignedRecord<R>> {
+        let entry = self.entries.remove(hash)?;
+        self.order.remove(&entry.priority);
+        let signer = &entry.record.signer;
+        if let Some(slots) = self.by_signer.get_mut(signer) {
+            slots.remove(&(entry.record.nonce, entry.priority));
+            if slots.is_empty() {
+                self.by_signer.remove(signer);
+            }
+        }
+        Some(entry.record)
+    }
+
+    /// Removes the records of `block`, just placed on `chain`, along with every record
+    /// whose nonce `chain` has now used, e.g. another record of the same signer and nonce.
+    ///
+    /// Returns how many records were removed. `Node::publish_block()` calls it for every
+    /// block it publishes; blocks placed on `chain` by other means need the same call.
+    pub fn remove_included<D: Database2, C: Consensus>(
+        &mut self,
+        block: &Block<R>,
+        chain: &BlockChain<D, C>,
+    ) -> usize {
+        let included = block
+            .get_signed_records()
+            .iter()
+            .filter_map(|record| self.remove(&record.hash().ok()?))
+            .count();
+        let stale: usize = block
+            .get_signed_records()
+            .iter()
+            .map(|record| self.purge(&record.signer, chain.next_nonce(&record.signer)))
+            .sum();
+        included + stale
+    }
+
+    /// Removes the records of `signer` with a nonce below `next`, returning how many
+    fn purge(&mut self, signer: &Signer, next: u64) -> usize {
+        let stale: Vec<Hash> = match self.by_signer.get(signer) {
+            Some(slots) => slots
+                .range(..(next, FIRST_PRIORITY))
+                .map(|(_, hash)| *hash)
+                .collect(),
+            None => return 0,
+        };
+        stale.iter().filter_map(|hash| self.remove(hash)).count()
+    }
+
+    /// Returns up to `max` records that can follow the tip of `chain` in a block, leaving
+    /// them in the pool, see `batch_after()`
+    pub fn batch<D: Database2, C: Consensus>(
+        &self,
+        max: usize,
+        chain: &BlockChain<D, C>,
+    ) -> Vec<SignedRecord<R>> {
+        self.batch_after(&[], max, chain)
+    }
+
+    /// Returns up to `max` records that can follow `preceding` in a block on top of `chain`,
+    /// leaving them in the pool.
+    ///
+    /// The records of a signer come in nonce order, starting from the nonce `chain` and
+    /// `preceding` expect next, and stop at the first missing nonce. Among the records that
+    /// can come next, the highest priority one is picked first; of the records sharing a
+    /// signer and nonce, only the highest priority one is picked.
+    pub fn batch_after<D: Database2, C: Consensus>(
+        &self,
+        preceding: &[SignedRecord<R>],
+        max: usize,
+        chain: &BlockChain<D, C>,
+    ) -> Vec<SignedRecord<R>> {
+        let mut next: HashMap<&Signer, u64> = HashMap::new();
+        for record in preceding {
+            next.insert(&record.signer, record.nonce + 1);
+        }
+
+        // The record each signer can place next, by priority
+        let mut ready: BTreeMap<Priority, &Hash> = self
+            .by_signer
+            .iter()
+            .filter_map(|(signer, slots)| {
+                let nonce = match next.get(signer) {
+                    Some(nonce) => *nonce,
+                    None => chain.next_nonce(signer),
+                };
+                Self::best_at(slots, nonce)
+            })
+            .collect();
+
+        let mut batch = vec![];
+        while batch.len() < max {
+            let record = match ready.pop_first() {
+                Some((_, hash)) => &self.entries[hash].record,
+                None => break,
+            };
+            if let Some((priority, hash)) =
+                Self::best_at(&self.by_signer[&record.signer], record.nonce + 1)
+            {
+                ready.insert(priority, hash);
+            }
+            batch.push(record.clone());
+        }
+        batch
+    }
+
+    /// Highest priority entry of `slots` carrying `nonce`
+    fn best_at(slots: &BTreeMap<SignerSlot, Hash>, nonce: u64) -> Option<(Priority, &Hash)> {
+        match slots.range((nonce, FIRST_PRIORITY)..).next() {
+            Some(((found, priority), hash)) if *found == nonce => Some((*priority, hash)),
+            _ => None,
+        }
+    }
+
+    fn pop_worst(&mut self) -> Option<SignedRecord<R>> {
+        let hash = *self.order.last_key_value()?.1;
+        self.remove(&hash)
+    }
+}
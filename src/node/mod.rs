@@ -5,9 +5,10 @@ use std::{
 };
 
 use crate::{
-    blockchain::{Block, BlockChain, FeedBack, Record, SignedRecord},
+    blockchain::{Block, BlockChain, FeedBack, Record},
     errs::CustomErrs,
     io::Database2,
+    mempool::MemPool,
     utils::Entity,
 };
 
@@ -29,7 +30,7 @@ pub struct Node<D: Database2, R: Record> {
     pub peers: HashSet<Box<dyn Entity<R>>>,
 
     /// A set of unconfirmed records held by this Node
-    pub mem_pool: Arc<Mutex<Vec<SignedRecord<R>>>>,
+    pub mem_pool: Arc<Mutex<MemPool<R>>>,
 
     /// A map of confirmed and published records cast and signed by each user
     /// Only records between members of this Node are kept within this node
@@ -48,6 +49,7 @@ pub struct Node<D: Database2, R: Record> {
 
 #[allow(unused)]
 impl<D: Database2, R: Record> Node<D, R> {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             chain: todo!(),
@@ -75,8 +77,16 @@ impl<D: Database2, R: Record> Node<D, R> {
         self.push_local(feed_back.get_block()).unwrap();
     }
 
+    /// Pushes the block onto the chain and drops its records, and the records whose nonces
+    /// it used, from the mem pool
     pub fn publish_block(&self, block: Block<R>) -> Result<FeedBack<R>, CustomErrs> {
-        self.chain.lock().unwrap().push(&block)
+        let mut chain = self.chain.lock().unwrap();
+        let feed_back = chain.push(&block)?;
+        self.mem_pool
+            .lock()
+            .unwrap()
+            .remove_included(feed_back.get_block(), &chain);
+        Ok(feed_back)
     }
 
     pub fn synchronize(&self) -> bool {
@@ -92,12 +102,6 @@ impl<D: Database2, R: Record> Node<D, R> {
 
 ///
 /// Struct for managing communications between entities and Nodes
-///
-///
-///
-///
-///
-
 pub struct Mng<D: Database2> {
     pub db: D,
 }
@@ -0,0 +1,779 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    errs::{CustomErrs, Source},
+    io::{Database2, DatabaseInsertable, QueryRange},
+};
+
+/// First bytes of every segment file
+const SEGMENT_MAGIC: &[u8; 8] = b"BCLOG\0\0\x01";
+/// First bytes of the index file
+const INDEX_MAGIC: &[u8; 8] = b"BCIDX\0\0\x01";
+const MAGIC_LEN: u64 = 8;
+/// Payload length, the payload's CRC-32 and the CRC-32 of these first 8 bytes, all
+/// little-endian `u32`s
+const FRAME_HEADER_LEN: u64 = 12;
+
+const INDEX_FILE: &str = "index";
+
+/// Size past which a new segment is started, unless given to `LogDB::with_segment_size()`
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Write recorded in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Op {
+    CreateTable(String),
+    Row {
+        table: String,
+        /// Value of the table's `key_column()`, if it has one
+        key: Option<String>,
+        columns: Vec<String>,
+    },
+}
+
+/// An `Op` without the row contents, enough to locate rows without reading the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum IndexedOp {
+    CreateTable(String),
+    Row { table: String, key: Option<String> },
+}
+
+impl From<&Op> for IndexedOp {
+    fn from(op: &Op) -> Self {
+        match op {
+            Op::CreateTable(table) => Self::CreateTable(table.clone()),
+            Op::Row { table, key, .. } => Self::Row {
+                table: table.clone(),
+                key: key.clone(),
+            },
+        }
+    }
+}
+
+/// Index entry describing one frame of the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    segment: u32,
+    offset: u64,
+    /// Length of the frame, header included
+    len: u64,
+    ops: Vec<IndexedOp>,
+}
+
+impl IndexEntry {
+    fn end(&self) -> (u32, u64) {
+        (self.segment, self.offset + self.len)
+    }
+}
+
+/// Where a row lives: the frame holding it and its position among the frame's ops
+#[derive(Debug, Clone, Copy)]
+struct RowLocation {
+    segment: u32,
+    offset: u64,
+    slot: usize,
+}
+
+/// How a run of frames ends
+#[derive(Debug, PartialEq, Eq)]
+enum Tail {
+    Clean,
+    /// The last frame was cut short by a crash while it was written
+    Torn,
+    /// A damaged frame is followed by more data
+    Corrupted,
+}
+
+/// Frames decoded from a file, as (offset, length, value), up to `valid_end`
+struct Frames<V> {
+    frames: Vec<(u64, u64, V)>,
+    valid_end: u64,
+    tail: Tail,
+}
+
+struct State {
+    /// Location of every committed row, by table and position
+    rows: HashMap<String, Vec<RowLocation>>,
+    /// Positions of the committed rows of a table, by key
+    keys: HashMap<(String, String), Vec<usize>>,
+    segment: u32,
+    segment_file: File,
+    segment_len: u64,
+    index_file: File,
+    readers: HashMap<u32, File>,
+    /// Writes of the current transaction, written as a single frame on commit
+    pending: Option<Vec<Op>>,
+}
+
+/// `Database2` stored as an append-only log of frames, for chains that are only ever
+/// appended to.
+///
+/// The log lives in a directory, split into segment files named `segment-NNNNNNNN.log`
+/// that start with an 8 byte magic number. Every write is appended to the last
+/// segment as a frame: the length of the payload, its CRC-32 and a CRC-32 of these two,
+/// all little-endian `u32`s, followed by the bincode encoded payload. A new segment is
+/// started once the current one reaches the segment size.
+///
+/// The `index` file next to the segments holds one frame per log frame, giving its
+/// location and the table and key of the rows it contains, so that opening the database
+/// and looking up a row by position or by `DatabaseInsertable::key_column()` (the hash
+/// of a published block) never scans the log. It can always be rebuilt from the log.
+///
+/// On open, a torn final frame left by a crash is truncated, and frames missing from
+/// the index are added to it. A frame is only taken for torn if its header is intact and
+/// the file ends before its payload does; any other damage fails with
+/// `CorruptedLogFile` rather than dropping the frames after it. A transaction is buffered in memory and written as a
+/// single frame on commit, so it is either fully stored or not at all.
+pub struct LogDB {
+    dir: PathBuf,
+    segment_size: u64,
+    tables: HashSet<String>,
+    state: RefCell<State>,
+    /// Tables when the current transaction began
+    journal: Option<HashSet<String>>,
+}
+
+impl LogDB {
+    /// Opens the log in the directory `dir`, creating it if needed
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, CustomErrs> {
+        Self::with_segment_size(dir, DEFAULT_SEGMENT_SIZE)
+    }
+
+    /// Opens the log in the directory `dir`, starting new segments past `segment_size` bytes
+    pub fn with_segment_size<P: AsRef<Path>>(
+        dir: P,
+        segment_size: u64,
+    ) -> Result<Self, CustomErrs> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|err| open_error(&dir, err))?;
+
+        let mut segments = segment_numbers(&dir)?;
+        let last = match segments.last() {
+            Some(last) => *last,
+            None => {
+                segments.push(0);
+                0
+            }
+        };
+
+        // Only the last segment can end with a torn frame, earlier ones were complete
+        // when the next one was started
+        let last_path = segment_path(&dir, last);
+        let bytes = read_file(&last_path, SEGMENT_MAGIC)?;
+        let recovered = parse_frames::<Vec<Op>>(&bytes, MAGIC_LEN);
+        if recovered.tail == Tail::Corrupted {
+            return Err(corrupted(&last_path, recovered.valid_end));
+        }
+        let segment_file = open_append(&last_path)?;
+        truncate(&last_path, &segment_file, recovered.valid_end)?;
+
+        let mut lengths = HashMap::new();
+        for segment in &segments {
+            let path = segment_path(&dir, *segment);
+            let len = fs::metadata(&path)
+                .map_err(|err| open_error(&path, err))?
+                .len();
+            lengths.insert(*segment, len);
+        }
+
+        let (entries, index_file) = load_index(&dir, &segments, &lengths)?;
+
+        let mut db = Self {
+            dir,
+            segment_size,
+            tables: HashSet::new(),
+            state: RefCell::new(State {
+                rows: HashMap::new(),
+                keys: HashMap::new(),
+                segment: last,
+                segment_file,
+                segment_len: recovered.valid_end,
+                index_file,
+                readers: HashMap::new(),
+                pending: None,
+            }),
+            journal: None,
+        };
+
+        let state = db.state.get_mut();
+        for entry in &entries {
+            state.index(entry);
+        }
+        db.tables = state.rows.keys().cloned().collect();
+
+        Ok(db)
+    }
+
+    /// Directory holding the segments and the index
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Appends `ops` to the log as one frame and indexes it, or buffers them if a
+    /// transaction is running
+    fn write(&self, ops: Vec<Op>) -> Result<(), Source> {
+        let mut state = self.state.borrow_mut();
+
+        if let Some(pending) = &mut state.pending {
+            pending.extend(ops);
+            return Ok(());
+        }
+
+        let entry = self.append(&mut state, &ops)?;
+        state.index(&entry);
+        Ok(())
+    }
+
+    fn append(&self, state: &mut State, ops: &[Op]) -> Result<IndexEntry, Source> {
+        let frame = encode_frame(&ops)?;
+        let len = frame.len() as u64;
+
+        if state.segment_len > MAGIC_LEN && state.segment_len + len > self.segment_size {
+            let segment = state.segment + 1;
+            let path = segment_path(&self.dir, segment);
+            fs::write(&path, SEGMENT_MAGIC)?;
+            state.segment_file = OpenOptions::new().append(true).open(&path)?;
+            state.segment = segment;
+            state.segment_len = MAGIC_LEN;
+        }
+
+        let offset = state.segment_len;
+        if let Err(err) = write_synced(&mut state.segment_file, &frame) {
+            // Leave no partial frame behind for the next write to follow
+            let _ = state.segment_file.set_len(offset);
+            return Err(err.into());
+        }
+        state.segment_len += len;
+
+        let entry = IndexEntry {
+            segment: state.segment,
+            offset,
+            len,
+            ops: ops.iter().map(IndexedOp::from).collect(),
+        };
+
+        // The frame is already durable, a missing index entry is restored on open
+        let _ = write_synced(&mut state.index_file, &encode_frame(&entry)?);
+
+        Ok(entry)
+    }
+}
+
+impl State {
+    /// Makes the tables and rows of a frame visible
+    fn index(&mut self, entry: &IndexEntry) {
+        for (slot, op) in entry.ops.iter().enumerate() {
+            match op {
+                IndexedOp::CreateTable(table) => {
+                    self.rows.entry(table.clone()).or_default();
+                }
+                IndexedOp::Row { table, key } => {
+                    let rows = self.rows.entry(table.clone()).or_default();
+                    if let Some(key) = key {
+                        self.keys
+                            .entry((table.clone(), key.clone()))
+                            .or_default()
+                            .push(rows.len());
+                    }
+                    rows.push(RowLocation {
+                        segment: entry.segment,
+                        offset: entry.offset,
+                        slot,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Reads the ops of the frame at `offset` in `segment`, on behalf of `table`
+    fn read_frame(
+        &mut self,
+        dir: &Path,
+        table: &str,
+        segment: u32,
+        offset: u64,
+    ) -> Result<Vec<Op>, CustomErrs> {
+        let read_error = |err: std::io::Error| CustomErrs::CouldNotReadFromDatabase {
+            table: table.to_owned(),
+            source: err.into(),
+        };
+
+        let file = match self.readers.entry(segment) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(File::open(segment_path(dir, segment)).map_err(read_error)?)
+            }
+        };
+
+        let mut header = [0; FRAME_HEADER_LEN as usize];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(read_error)?;
+
+        let (len, crc) =
+            split_header(&header).ok_or_else(|| corrupted(&segment_path(dir, segment), offset))?;
+        let mut payload = vec![0; len];
+        file.read_exact(&mut payload).map_err(read_error)?;
+
+        match bincode::deserialize(&payload) {
+            Ok(ops) if crc32fast::hash(&payload) == crc => Ok(ops),
+            _ => Err(corrupted(&segment_path(dir, segment), offset)),
+        }
+    }
+
+    /// Reads the rows of `table` at `locations`, decoding each frame once for
+    /// consecutive rows
+    fn read_rows(
+        &mut self,
+        dir: &Path,
+        table: &str,
+        locations: &[RowLocation],
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        let mut frame: Option<(u32, u64, Vec<Op>)> = None;
+        let mut rows = Vec::with_capacity(locations.len());
+
+        for location in locations {
+            let ops = match &frame {
+                Some((segment, offset, ops))
+                    if *segment == location.segment && *offset == location.offset =>
+                {
+                    ops
+                }
+                _ => {
+                    let ops = self.read_frame(dir, table, location.segment, location.offset)?;
+                    &frame.insert((location.segment, location.offset, ops)).2
+                }
+            };
+
+            match ops.get(location.slot) {
+                Some(Op::Row { columns, .. }) => rows.push(columns.clone()),
+                _ => {
+                    let path = segment_path(dir, location.segment);
+                    return Err(corrupted(&path, location.offset));
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Rows of `table` written by the current transaction
+    fn pending_rows<'a>(&'a self, table: &'a str) -> impl Iterator<Item = &'a Vec<String>> + 'a {
+        self.pending
+            .iter()
+            .flatten()
+            .filter_map(move |op| match op {
+                Op::Row {
+                    table: t, columns, ..
+                } if t == table => Some(columns),
+                _ => None,
+            })
+    }
+}
+
+impl Database2 for LogDB {
+    fn create_table<T: DatabaseInsertable>(&mut self) -> Result<(), CustomErrs> {
+        let table_name = T::get_name();
+
+        let exists = {
+            let state = self.state.get_mut();
+            state.rows.contains_key(table_name)
+                || state
+                    .pending
+                    .iter()
+                    .flatten()
+                    .any(|op| matches!(op, Op::CreateTable(t) if t == table_name))
+        };
+
+        if !exists {
+            self.write(vec![Op::CreateTable(table_name.to_owned())])
+                .map_err(|source| CustomErrs::CannotCreateSuchTable {
+                    table: table_name.to_owned(),
+                    source,
+                })?;
+        }
+
+        self.tables.insert(table_name.to_owned());
+        Ok(())
+    }
+
+    fn len(&self, table_name: &str) -> Result<i64, CustomErrs> {
+        let state = self.state.borrow();
+        let committed = state.rows.get(table_name).map_or(0, Vec::len);
+        Ok((committed + state.pending_rows(table_name).count()) as i64)
+    }
+
+    fn get_tables(&self) -> &HashSet<String> {
+        &self.tables
+    }
+
+    fn get_tables_mut(&mut self) -> &mut HashSet<String> {
+        &mut self.tables
+    }
+
+    fn insert_row<T: DatabaseInsertable>(&self, items: &[String]) -> Result<(), CustomErrs> {
+        let table_name = T::get_name();
+        let columns = T::columns();
+        if !self.table_exists::<T>() {
+            return Err(CustomErrs::NoSuchTableInDatabase {
+                table: table_name.to_owned(),
+            });
+        }
+        if items.len() != columns.len() {
+            return Err(CustomErrs::ColumnCountMismatch {
+                table: table_name.to_owned(),
+                expected: columns.len(),
+                found: items.len(),
+            });
+        }
+
+        let key = T::key_column()
+            .and_then(|key| columns.iter().position(|c| *c == key))
+            .map(|i| items[i].clone());
+
+        self.write(vec![Op::Row {
+            table: table_name.to_owned(),
+            key,
+            columns: items.to_vec(),
+        }])
+        .map_err(|source| CustomErrs::CouldNotInsertRowIntoDatabase {
+            table: table_name.to_owned(),
+            source,
+        })
+    }
+
+    fn get_rows<T: DatabaseInsertable>(
+        &self,
+        range: QueryRange,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        if range.is_empty() || range.begin < 0 {
+            return Ok(vec![]);
+        }
+
+        let table_name = T::get_name();
+        let begin = range.begin as usize;
+        let count = range.len() as usize;
+
+        let mut state = self.state.borrow_mut();
+        let committed = state.rows.get(table_name).map_or(&[][..], Vec::as_slice);
+        let committed_len = committed.len();
+        let locations: Vec<RowLocation> =
+            committed.iter().skip(begin).take(count).copied().collect();
+
+        // Rows written by the current transaction follow the committed ones
+        let mut rows = state.read_rows(&self.dir, table_name, &locations)?;
+        let missing = count - rows.len();
+        rows.extend(
+            state
+                .pending_rows(table_name)
+                .skip(begin.saturating_sub(committed_len))
+                .take(missing)
+                .cloned(),
+        );
+
+        Ok(rows)
+    }
+
+    fn find_rows<T: DatabaseInsertable>(
+        &self,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        let table_name = T::get_name();
+        let index = T::columns()
+            .iter()
+            .position(|c| *c == column)
+            .ok_or_else(|| CustomErrs::NoSuchColumnInTable {
+                table: table_name.to_owned(),
+                column: column.to_owned(),
+            })?;
+
+        let mut state = self.state.borrow_mut();
+        let committed = state.rows.get(table_name).map_or(&[][..], Vec::as_slice);
+
+        let mut rows = if T::key_column() == Some(column) {
+            let locations: Vec<RowLocation> = state
+                .keys
+                .get(&(table_name.to_owned(), value.to_owned()))
+                .map(|positions| positions.iter().map(|p| committed[*p]).collect())
+                .unwrap_or_default();
+            state.read_rows(&self.dir, table_name, &locations)?
+        } else {
+            let locations = committed.to_vec();
+            let mut rows = state.read_rows(&self.dir, table_name, &locations)?;
+            rows.retain(|row| row[index] == value);
+            rows
+        };
+
+        rows.extend(
+            state
+                .pending_rows(table_name)
+                .filter(|row| row[index] == value)
+                .cloned(),
+        );
+
+        Ok(rows)
+    }
+
+    fn begin(&mut self) -> Result<(), CustomErrs> {
+        if self.journal.is_some() {
+            return Err(CustomErrs::TransactionAlreadyInProgress);
+        }
+
+        self.state.get_mut().pending = Some(vec![]);
+        self.journal = Some(self.tables.clone());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), CustomErrs> {
+        let tables = self
+            .journal
+            .take()
+            .ok_or(CustomErrs::NoTransactionInProgress)?;
+        let ops = self.state.get_mut().pending.take().unwrap_or_default();
+
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        self.write(ops).map_err(|source| {
+            self.tables = tables;
+            CustomErrs::DatabaseTransactionFailed { source }
+        })
+    }
+
+    fn rollback(&mut self) -> Result<(), CustomErrs> {
+        self.tables = self
+            .journal
+            .take()
+            .ok_or(CustomErrs::NoTransactionInProgress)?;
+        self.state.get_mut().pending = None;
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("segment-{:08}.log", segment))
+}
+
+/// Numbers of the segments in `dir`, in ascending order
+fn segment_numbers(dir: &Path) -> Result<Vec<u32>, CustomErrs> {
+    let mut segments = vec![];
+
+    for entry in fs::read_dir(dir).map_err(|err| open_error(dir, err))? {
+        let entry = entry.map_err(|err| open_error(dir, err))?;
+        let name = entry.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("segment-"))
+            .and_then(|name| name.strip_suffix(".log"))
+            .and_then(|number| number.parse().ok());
+
+        if let Some(number) = number {
+            segments.push(number);
+        }
+    }
+
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+/// Reads the file at `path`, creating it with `magic` if it is missing or was cut off
+/// before its magic number was written.
+///
+/// Any other error reading the file is returned, so that a file which could not be read
+/// is never overwritten.
+fn read_file(path: &Path, magic: &[u8; 8]) -> Result<Vec<u8>, CustomErrs> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+        Err(err) => return Err(open_error(path, err)),
+    };
+
+    if bytes.len() < magic.len() {
+        fs::write(path, magic).map_err(|err| open_error(path, err))?;
+        return Ok(magic.to_vec());
+    }
+
+    if &bytes[..magic.len()] != magic {
+        return Err(corrupted(path, 0));
+    }
+
+    Ok(bytes)
+}
+
+fn open_append(path: &Path) -> Result<File, CustomErrs> {
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|err| open_error(path, err))
+}
+
+fn truncate(path: &Path, file: &File, len: u64) -> Result<(), CustomErrs> {
+    file.set_len(len)
+        .and_then(|_| file.sync_all())
+        .map_err(|err| open_error(path, err))
+}
+
+fn open_error<E: Into<Source>>(path: &Path, err: E) -> CustomErrs {
+    CustomErrs::CannotEstablishDatabaseConnection {
+        path: path.display().to_string(),
+        source: err.into(),
+    }
+}
+
+fn corrupted(path: &Path, offset: u64) -> CustomErrs {
+    CustomErrs::CorruptedLogFile {
+        path: path.display().to_string(),
+        offset,
+    }
+}
+
+fn write_synced(file: &mut File, bytes: &[u8]) -> std::io::Result<()> {
+    file.write_all(bytes)?;
+    file.sync_data()
+}
+
+fn encode_frame<V: Serialize>(value: &V) -> Result<Vec<u8>, Source> {
+    let payload = bincode::serialize(value)?;
+    let len = u32::try_from(payload.len())?;
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN as usize + payload.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&frame).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+/// Payload length and CRC-32 of a frame header, or `None` if the header is damaged
+fn split_header(header: &[u8]) -> Option<(usize, u32)> {
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let header_crc = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    (crc32fast::hash(&header[..8]) == header_crc).then_some((len as usize, crc))
+}
+
+/// Decodes the frames of `bytes` from `start` until the end or the first damaged frame
+fn parse_frames<V: DeserializeOwned>(bytes: &[u8], start: u64) -> Frames<V> {
+    let mut frames = vec![];
+    let mut position = start as usize;
+
+    let tail = loop {
+        let rest = &bytes[position..];
+        if rest.is_empty() {
+            break Tail::Clean;
+        }
+        if rest.len() < FRAME_HEADER_LEN as usize {
+            break Tail::Torn;
+        }
+
+        // A damaged length could make any frame look like the last one
+        let (len, crc) = match split_header(rest) {
+            Some(header) => header,
+            None => break Tail::Corrupted,
+        };
+        let end = FRAME_HEADER_LEN as usize + len;
+        if rest.len() < end {
+            break Tail::Torn;
+        }
+
+        let payload = &rest[FRAME_HEADER_LEN as usize..end];
+        let value = match bincode::deserialize(payload) {
+            Ok(value) if crc32fast::hash(payload) == crc => value,
+            // A frame cut short by a crash is the last one of the file
+            _ if rest.len() == end => break Tail::Torn,
+            _ => break Tail::Corrupted,
+        };
+
+        frames.push((position as u64, end as u64, value));
+        position += end;
+    };
+
+    Frames {
+        frames,
+        valid_end: position as u64,
+        tail,
+    }
+}
+
+/// Loads the index of the segments in `dir`, dropping entries the log does not back and
+/// adding the frames it is missing.
+///
+/// Returns the index entries in log order along with the index file, open for appending.
+fn load_index(
+    dir: &Path,
+    segments: &[u32],
+    lengths: &HashMap<u32, u64>,
+) -> Result<(Vec<IndexEntry>, File), CustomErrs> {
+    let path = dir.join(INDEX_FILE);
+    let bytes = read_file(&path, INDEX_MAGIC)?;
+    let parsed = parse_frames::<IndexEntry>(&bytes, MAGIC_LEN);
+
+    // The index can always be rebuilt, so a damaged one is dropped rather than reported
+    let frames = if parsed.tail == Tail::Corrupted {
+        vec![]
+    } else {
+        parsed.frames
+    };
+
+    let mut entries: Vec<IndexEntry> = vec![];
+    let mut index_len = MAGIC_LEN;
+    for (offset, len, entry) in frames {
+        let previous_end = entries.last().map_or((segments[0], MAGIC_LEN), |e| e.end());
+        let backed = lengths
+            .get(&entry.segment)
+            .is_some_and(|segment_len| entry.offset + entry.len <= *segment_len);
+
+        if !backed || (entry.segment, entry.offset) < previous_end {
+            break;
+        }
+
+        entries.push(entry);
+        index_len = offset + len;
+    }
+
+    let mut index_file = open_append(&path)?;
+    truncate(&path, &index_file, index_len)?;
+
+    // Frames written after the index was last updated
+    let (resume_segment, resume_offset) =
+        entries.last().map_or((segments[0], MAGIC_LEN), |e| e.end());
+    for segment in segments.iter().filter(|s| **s >= resume_segment) {
+        let segment_path = segment_path(dir, *segment);
+        let bytes = read_file(&segment_path, SEGMENT_MAGIC)?;
+        let start = if *segment == resume_segment {
+            resume_offset
+        } else {
+            MAGIC_LEN
+        };
+
+        let parsed = parse_frames::<Vec<Op>>(&bytes, start);
+        if parsed.tail != Tail::Clean {
+            return Err(corrupted(&segment_path, parsed.valid_end));
+        }
+
+        for (offset, len, ops) in parsed.frames {
+            let entry = IndexEntry {
+                segment: *segment,
+                offset,
+                len,
+                ops: ops.iter().map(IndexedOp::from).collect(),
+            };
+            encode_frame(&entry)
+                .and_then(|frame| Ok(write_synced(&mut index_file, &frame)?))
+                .map_err(|err| open_error(&path, err))?;
+            entries.push(entry);
+        }
+    }
+
+    Ok((entries, index_file))
+}
@@ -1,16 +1,29 @@
-use std::collections::HashSet;
+mod logdb;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
 use rusqlite::{params, Connection, ToSql};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     block,
-    blockchain::{Block, FeedBack, PublishedBlock, Record, SignedRecord},
+    blockchain::{
+        Block, BlockChain, BlockHeader, FeedBack, MultiSigProposal, PublishedBlock, Record,
+        SignedRecord,
+    },
+    consensus::Consensus,
     errs::CustomErrs,
-    io::{Database, Database2, DatabaseInsertable},
+    gen::{Hash, PrivateKey, PublicKey},
+    io::{Database, Database2, DatabaseInsertable, QueryRange},
+    mempool::{MemPool, Scorer},
     node::NodeId,
 };
 
+pub use logdb::{LogDB, DEFAULT_SEGMENT_SIZE};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transaction {
     src: String,
@@ -19,9 +32,31 @@ pub struct Transaction {
 }
 
 pub trait Entity<T: Record> {
-    fn public_key(&self) -> &[u8];
-    fn sign_record(&self, record: T, pkey: &[u8]) -> Result<SignedRecord<T>, CustomErrs> {
-        record.sign(pkey, &self.public_key())
+    fn public_key(&self) -> &PublicKey;
+    /// Signs `record` with `private_key`, which must be the key of this entity
+    fn sign_record(
+        &self,
+        record: T,
+        private_key: &PrivateKey,
+        chain_id: &Hash,
+        nonce: u64,
+    ) -> Result<SignedRecord<T>, CustomErrs> {
+        if private_key.public_key() != *self.public_key() {
+            return Err(CustomErrs::KeyMismatch);
+        }
+        record.sign(private_key, chain_id, nonce)
+    }
+    /// Adds the signature of `private_key`, which must be the key of this entity, to a record
+    /// proposed for a `SignerSet` this entity is part of
+    fn co_sign(
+        &self,
+        proposal: &mut MultiSigProposal<T>,
+        private_key: &PrivateKey,
+    ) -> Result<(), CustomErrs> {
+        if private_key.public_key() != *self.public_key() {
+            return Err(CustomErrs::KeyMismatch);
+        }
+        proposal.sign(private_key)
     }
     fn receive_broadcast(&self, block: &FeedBack<T>, from_node: NodeId);
 }
@@ -32,7 +67,7 @@ pub struct Miner<T: Record> {
 
 impl<T: Record> Miner<T> {
     ///Initializes a new instance of Miner with an empty block
-
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self { block: block![] }
     }
@@ -45,10 +80,41 @@ impl<T: Record> Miner<T> {
         self.block.append(record);
     }
 
+    /// Adds the highest priority records of `mem_pool` that can follow the block's records
+    /// on top of `chain` until it holds `max_size` records, returning how many were added.
+    ///
+    /// Records stay in the pool until the block is published, see `MemPool::batch_after()`.
+    pub fn fill_from<S: Scorer<T>, D: Database2, C: Consensus>(
+        &mut self,
+        mem_pool: &MemPool<T, S>,
+        chain: &BlockChain<D, C>,
+        max_size: usize,
+    ) -> usize {
+        let room = max_size.saturating_sub(self.block.signed_records.len());
+        let batch = mem_pool.batch_after(&self.block.signed_records, room, chain);
+        let added = batch.len();
+        batch
+            .into_iter()
+            .for_each(|record| self.block.append(record));
+        added
+    }
+
+    /// Seals the collected records on top of `chain`'s tip with the chain's consensus engine.
+    ///
+    /// On a proof-of-work chain this searches nonces until the header meets the target.
+    /// Publish the result with `chain.push_with_header(header, miner.get_block())`.
+    pub fn mine<D: Database2, C: Consensus>(
+        &self,
+        chain: &BlockChain<D, C>,
+    ) -> Result<BlockHeader, CustomErrs> {
+        chain.seal(&self.block)
+    }
+
     /// Returns `Ok(())` if record is valid or an `CustomErrs` variant
     ///matching the type of failure
     pub fn verify_record(&self, record: SignedRecord<T>) -> Result<(), CustomErrs> {
-        record.verify()
+        record.verify()?;
+        record.get_record().validate()
     }
 }
 
@@ -69,8 +135,12 @@ pub struct SqliteDB {
 impl SqliteDB {
     pub fn open(path: &str) -> Result<Self, CustomErrs> {
         Ok(Self {
-            con: Connection::open(path)
-                .map_err(|_| CustomErrs::CannotEstablishDatabaseConnection)?,
+            con: Connection::open(path).map_err(|err| {
+                CustomErrs::CannotEstablishDatabaseConnection {
+                    path: path.to_owned(),
+                    source: err.into(),
+                }
+            })?,
         })
     }
 
@@ -79,32 +149,51 @@ impl SqliteDB {
         record: &SignedRecord<R>,
         stamp: i64,
     ) -> Result<(), CustomErrs> {
-        let rstring: String = serde_json::to_string(record.get_record()).unwrap();
-        let signature = serde_json::to_string(record.get_signature().as_ref()).unwrap();
-        let id = serde_json::to_string(record.get_signer()).unwrap();
+        let serialize_error =
+            |err: serde_json::Error| CustomErrs::CouldNotSerialize { source: err.into() };
+        let rstring = serde_json::to_string(record.get_record()).map_err(serialize_error)?;
+        let signature = serde_json::to_string(record.get_signatures()).map_err(serialize_error)?;
+        let id = serde_json::to_string(record.get_signer()).map_err(serialize_error)?;
 
         self.con
             .execute(
                 "INSERT INTO records (Position, Record, Identity, Signature) VALUES (?, ?, ?, ?)",
                 params![stamp, rstring, id, signature],
             )
-            .map_err(|_| CustomErrs::CouldNotInsertRecordsIntoDatabase)?;
+            .map_err(|err| CustomErrs::CouldNotInsertRecordsIntoDatabase { source: err.into() })?;
 
         Ok(())
     }
 }
 
-impl Record for Transaction {}
+impl Record for Transaction {
+    const RECORD_TYPE: &'static str = "transaction";
+
+    /// Both parties must be named and the amount must be a whole number
+    fn validate(&self) -> Result<(), CustomErrs> {
+        let reason = if self.src.is_empty() || self.dst.is_empty() {
+            "missing party".to_owned()
+        } else if self.amount.parse::<u64>().is_err() {
+            format!("invalid amount {:?}", self.amount)
+        } else {
+            return Ok(());
+        };
+        Err(CustomErrs::RecordRejected { reason })
+    }
+}
 
 impl Database<Transaction> for SqliteDB {
     fn establish_connection(&self) -> Result<(), CustomErrs> {
         Ok(())
     }
 
-    fn next_stamp(&self) -> i64 {
-        let mut stmt = self.con.prepare(&"SELECT COUNT(*) FROM records").unwrap();
-        let count = stmt.query_row([], |row| row.get(0)).unwrap();
-        count
+    fn next_stamp(&self) -> Result<i64, CustomErrs> {
+        self.con
+            .query_row("SELECT COUNT(*) FROM records", [], |row| row.get(0))
+            .map_err(|err| CustomErrs::CouldNotReadFromDatabase {
+                table: "records".to_owned(),
+                source: err.into(),
+            })
     }
 
     fn insert_row(&self, record: &SignedRecord<Transaction>, stamp: i64) -> Result<(), CustomErrs> {
@@ -112,14 +201,14 @@ impl Database<Transaction> for SqliteDB {
     }
 
     fn insert_hash(&self, published_block: PublishedBlock) -> Result<(), CustomErrs> {
-        let s = published_block.into_iter().next().unwrap();
+        let s = published_block.to_vec()?;
 
         self.con
             .execute(
                 "INSERT INTO hash (Hash, BlockPosition) VALUES (?, ?)",
                 params![s[0], s[1]],
             )
-            .map_err(|_| CustomErrs::CouldNotInsertHashIntoDatabase)?;
+            .map_err(|err| CustomErrs::CouldNotInsertHashIntoDatabase { source: err.into() })?;
 
         Ok(())
     }
@@ -130,11 +219,56 @@ pub struct SqliteDB2 {
     connection: Connection,
 }
 impl SqliteDB2 {
-    pub fn new(path: &str) -> Self {
-        Self {
-            tables: HashSet::new(),
-            connection: Connection::open(path).unwrap(),
+    /// Opens the database at `path`, picking up any tables it already contains
+    pub fn new(path: &str) -> Result<Self, CustomErrs> {
+        let open_error = |err: rusqlite::Error| CustomErrs::CannotEstablishDatabaseConnection {
+            path: path.to_owned(),
+            source: err.into(),
+        };
+        let connection = Connection::open(path).map_err(open_error)?;
+        let tables = Self::existing_tables(&connection).map_err(open_error)?;
+        Ok(Self { tables, connection })
+    }
+
+    fn existing_tables(connection: &Connection) -> rusqlite::Result<HashSet<String>> {
+        let mut stmt = connection.prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names.collect()
+    }
+
+    fn query_rows<T: DatabaseInsertable>(
+        &self,
+        filter: &str,
+        limit: &str,
+        params: &[&dyn ToSql],
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        if !self.table_exists::<T>() {
+            return Ok(vec![]);
         }
+
+        let columns = T::columns();
+        let sql = format!(
+            "SELECT {} FROM {} {} ORDER BY Position {}",
+            columns.join(", "),
+            T::get_name(),
+            filter,
+            limit
+        );
+
+        let read = || -> rusqlite::Result<Vec<Vec<String>>> {
+            let mut stmt = self.connection.prepare(&sql)?;
+            let rows = stmt.query_map(params, |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, String>(i))
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })?;
+            rows.collect()
+        };
+
+        read().map_err(|err| CustomErrs::CouldNotReadFromDatabase {
+            table: T::get_name().to_owned(),
+            source: err.into(),
+        })
     }
 }
 
@@ -156,22 +290,28 @@ impl Database2 for SqliteDB2 {
             column_defs.join(", ")
         );
 
-        self.connection
-            .execute(&create_stmt, [])
-            .map_err(|_| CustomErrs::CannotCreateSuchTable)?;
+        self.connection.execute(&create_stmt, []).map_err(|err| {
+            CustomErrs::CannotCreateSuchTable {
+                table: table_name.to_owned(),
+                source: err.into(),
+            }
+        })?;
 
         self.tables.insert(table_name.to_string());
 
         Ok(())
     }
 
-    fn len(&self, table_name: &str) -> i64 {
-        let mut stmt = self
-            .connection
-            .prepare(&format!("SELECT COUNT(*) FROM {}", table_name))
-            .unwrap();
-        let count = stmt.query_row([], |row| row.get(0)).unwrap();
-        count
+    /// Rows are never deleted and `AUTOINCREMENT` numbers them from 1 without gaps, a
+    /// rolled back transaction giving its numbers back, so the last position is the length
+    fn len(&self, table_name: &str) -> Result<i64, CustomErrs> {
+        let sql = format!("SELECT COALESCE(MAX(Position), 0) FROM {}", table_name);
+        self.connection
+            .query_row(&sql, [], |row| row.get(0))
+            .map_err(|err| CustomErrs::CouldNotReadFromDatabase {
+                table: table_name.to_owned(),
+                source: err.into(),
+            })
     }
 
     fn get_tables(&self) -> &HashSet<String> {
@@ -184,16 +324,21 @@ impl Database2 for SqliteDB2 {
         let num_columns = columns.len();
 
         let placeholders = vec!["?"; num_columns].join(", ");
-        let column_names = vec![columns[0..].join(", ")].join(", ");
+        let column_names = columns.join(", ");
         let sql = format!(
             "INSERT INTO {} ({}) VALUES ({})",
             table_name, column_names, placeholders
         );
 
-        let mut stmt = self.connection.prepare(&sql).unwrap();
         let params = rusqlite::params_from_iter(items.iter().map(|x| x as &dyn ToSql));
 
-        stmt.execute(params).unwrap();
+        self.connection
+            .prepare(&sql)
+            .and_then(|mut stmt| stmt.execute(params))
+            .map_err(|err| CustomErrs::CouldNotInsertRowIntoDatabase {
+                table: table_name.to_owned(),
+                source: err.into(),
+            })?;
 
         Ok(())
     }
@@ -201,4 +346,220 @@ impl Database2 for SqliteDB2 {
     fn get_tables_mut(&mut self) -> &mut HashSet<String> {
         &mut self.tables
     }
+
+    fn get_rows<T: DatabaseInsertable>(
+        &self,
+        range: QueryRange,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        if range.is_empty() || range.begin < 0 {
+            return Ok(vec![]);
+        }
+
+        // Positions count from 1 and are never reused, see `len()`
+        self.query_rows::<T>(
+            "WHERE Position BETWEEN ?1 AND ?2",
+            "",
+            &[&(range.begin + 1), &(range.end + 1)],
+        )
+    }
+
+    fn find_rows<T: DatabaseInsertable>(
+        &self,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        if !T::columns().contains(&column) {
+            return Err(CustomErrs::NoSuchColumnInTable {
+                table: T::get_name().to_owned(),
+                column: column.to_owned(),
+            });
+        }
+
+        self.query_rows::<T>(&format!("WHERE {} = ?1", column), "", &[&value])
+    }
+
+    fn begin(&mut self) -> Result<(), CustomErrs> {
+        self.connection
+            .execute_batch("BEGIN IMMEDIATE")
+            .map_err(|err| CustomErrs::DatabaseTransactionFailed { source: err.into() })
+    }
+
+    fn commit(&mut self) -> Result<(), CustomErrs> {
+        self.connection
+            .execute_batch("COMMIT")
+            .map_err(|err| CustomErrs::DatabaseTransactionFailed { source: err.into() })
+    }
+
+    fn rollback(&mut self) -> Result<(), CustomErrs> {
+        self.connection
+            .execute_batch("ROLLBACK")
+            .map_err(|err| CustomErrs::DatabaseTransactionFailed { source: err.into() })?;
+
+        // Tables created inside the transaction are gone as well
+        self.tables = Self::existing_tables(&self.connection).map_err(|err| {
+            CustomErrs::CouldNotReadFromDatabase {
+                table: "sqlite_master".to_owned(),
+                source: err.into(),
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Rows of every table of an `InMemoryDB`, keyed by table name
+type Tables = HashMap<String, Vec<Vec<String>>>;
+
+/// `Database2` kept entirely in memory, for tests and short-lived chains.
+///
+/// Behaves like `SqliteDB2`: zero-based positions, rows returned in insertion order,
+/// inserts into missing tables fail, and transactions do not nest. Nothing survives
+/// the value being dropped.
+#[derive(Debug, Default)]
+pub struct InMemoryDB {
+    tables: HashSet<String>,
+    rows: RefCell<Tables>,
+    /// Tables and their lengths when the current transaction began
+    journal: Option<(HashSet<String>, HashMap<String, usize>)>,
+}
+
+impl InMemoryDB {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Database2 for InMemoryDB {
+    fn create_table<T: DatabaseInsertable>(&mut self) -> Result<(), CustomErrs> {
+        let table_name = T::get_name().to_owned();
+        self.rows.get_mut().entry(table_name.clone()).or_default();
+        self.tables.insert(table_name);
+        Ok(())
+    }
+
+    fn len(&self, table_name: &str) -> Result<i64, CustomErrs> {
+        Ok(self
+            .rows
+            .borrow()
+            .get(table_name)
+            .map_or(0, |rows| rows.len() as i64))
+    }
+
+    fn get_tables(&self) -> &HashSet<String> {
+        &self.tables
+    }
+
+    fn get_tables_mut(&mut self) -> &mut HashSet<String> {
+        &mut self.tables
+    }
+
+    fn insert_row<T: DatabaseInsertable>(&self, items: &[String]) -> Result<(), CustomErrs> {
+        let table_name = T::get_name();
+        if items.len() != T::columns().len() {
+            return Err(CustomErrs::ColumnCountMismatch {
+                table: table_name.to_owned(),
+                expected: T::columns().len(),
+                found: items.len(),
+            });
+        }
+
+        match self.rows.borrow_mut().get_mut(table_name) {
+            Some(rows) => {
+                rows.push(items.to_vec());
+                Ok(())
+            }
+            None => Err(CustomErrs::NoSuchTableInDatabase {
+                table: table_name.to_owned(),
+            }),
+        }
+    }
+
+    fn get_rows<T: DatabaseInsertable>(
+        &self,
+        range: QueryRange,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        if range.is_empty() || range.begin < 0 {
+            return Ok(vec![]);
+        }
+
+        let rows = self.rows.borrow();
+        let table = match rows.get(T::get_name()) {
+            Some(table) => table,
+            None => return Ok(vec![]),
+        };
+
+        Ok(table
+            .iter()
+            .skip(range.begin as usize)
+            .take(range.len() as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn find_rows<T: DatabaseInsertable>(
+        &self,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        let index = T::columns()
+            .iter()
+            .position(|c| *c == column)
+            .ok_or_else(|| CustomErrs::NoSuchColumnInTable {
+                table: T::get_name().to_owned(),
+                column: column.to_owned(),
+            })?;
+
+        Ok(self
+            .rows
+            .borrow()
+            .get(T::get_name())
+            .map(|table| {
+                table
+                    .iter()
+                    .filter(|row| row[index] == value)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn begin(&mut self) -> Result<(), CustomErrs> {
+        if self.journal.is_some() {
+            return Err(CustomErrs::TransactionAlreadyInProgress);
+        }
+
+        let lengths = self
+            .rows
+            .get_mut()
+            .iter()
+            .map(|(name, rows)| (name.clone(), rows.len()))
+            .collect();
+        self.journal = Some((self.tables.clone(), lengths));
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), CustomErrs> {
+        self.journal
+            .take()
+            .map(|_| ())
+            .ok_or(CustomErrs::NoTransactionInProgress)
+    }
+
+    fn rollback(&mut self) -> Result<(), CustomErrs> {
+        let (tables, lengths) = self
+            .journal
+            .take()
+            .ok_or(CustomErrs::NoTransactionInProgress)?;
+
+        // Rows are only ever appended, so cutting every table back to its length
+        // at `begin()` undoes the transaction
+        let rows = self.rows.get_mut();
+        rows.retain(|name, _| lengths.contains_key(name));
+        for (name, table) in rows.iter_mut() {
+            table.truncate(lengths[name]);
+        }
+        self.tables = tables;
+
+        Ok(())
+    }
 }
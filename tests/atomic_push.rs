@@ -0,0 +1,153 @@
+//! A block whose chain entry cannot be written leaves no records behind
+
+use std::{cell::Cell, collections::HashSet, fs, io, rc::Rc};
+
+use blockchain::{
+    blockchain::{Block, BlockChain, Genesis, PublishedBlock, Record},
+    errs::CustomErrs,
+    gen::{self, HashAlgorithm},
+    io::{Database2, DatabaseInsertable, QueryRange},
+    utils::{InMemoryDB, SqliteDB2, Transaction},
+};
+
+/// Wraps a database, failing every write of a chain entry while `fail` is set
+struct FailingDB<D> {
+    inner: D,
+    fail: Rc<Cell<bool>>,
+}
+
+impl<D: Database2> Database2 for FailingDB<D> {
+    fn create_table<T: DatabaseInsertable>(&mut self) -> Result<(), CustomErrs> {
+        self.inner.create_table::<T>()
+    }
+
+    fn len(&self, table_name: &str) -> Result<i64, CustomErrs> {
+        self.inner.len(table_name)
+    }
+
+    fn get_tables_mut(&mut self) -> &mut HashSet<String> {
+        self.inner.get_tables_mut()
+    }
+
+    fn get_tables(&self) -> &HashSet<String> {
+        self.inner.get_tables()
+    }
+
+    fn insert_row<T: DatabaseInsertable>(&self, columns: &[String]) -> Result<(), CustomErrs> {
+        if self.fail.get() && T::get_name() == <&PublishedBlock>::get_name() {
+            return Err(CustomErrs::CouldNotInsertRowIntoDatabase {
+                table: T::get_name().to_owned(),
+                source: io::Error::other("disk full").into(),
+            });
+        }
+        self.inner.insert_row::<T>(columns)
+    }
+
+    fn get_rows<T: DatabaseInsertable>(
+        &self,
+        range: QueryRange,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        self.inner.get_rows::<T>(range)
+    }
+
+    fn find_rows<T: DatabaseInsertable>(
+        &self,
+        column: &str,
+        value: &str,
+    ) -> Result<Vec<Vec<String>>, CustomErrs> {
+        self.inner.find_rows::<T>(column, value)
+    }
+
+    fn begin(&mut self) -> Result<(), CustomErrs> {
+        self.inner.begin()
+    }
+
+    fn commit(&mut self) -> Result<(), CustomErrs> {
+        self.inner.commit()
+    }
+
+    fn rollback(&mut self) -> Result<(), CustomErrs> {
+        self.inner.rollback()
+    }
+}
+
+fn genesis() -> Genesis {
+    Genesis::new("atomic")
+}
+
+fn block(count: u64) -> Block<Transaction> {
+    let key_pair = gen::generate_key_pair();
+    Block {
+        signed_records: (0..count)
+            .map(|nonce| {
+                Transaction::new("A", "B", "1")
+                    .sign(key_pair.private_key(), &genesis().chain_id(), nonce)
+                    .unwrap()
+            })
+            .collect(),
+    }
+}
+
+/// Pushes a block while the chain entry cannot be written, then two blocks that are
+/// accepted and must take the positions the failed block would have had
+fn push_after_failure<D: Database2>(database: D) {
+    let fail = Rc::new(Cell::new(false));
+    let database = FailingDB {
+        inner: database,
+        fail: fail.clone(),
+    };
+    let mut chain = BlockChain::open(database, genesis()).unwrap();
+
+    fail.set(true);
+    assert!(matches!(
+        chain.push(&block(3)),
+        Err(CustomErrs::CouldNotInsertRowIntoDatabase { .. })
+    ));
+    assert_eq!(chain.tip().unwrap().unwrap().get_header().height, 0);
+    assert!(chain
+        .get_records::<Transaction>(QueryRange::new(0, 0))
+        .is_err());
+    fail.set(false);
+
+    let first = block(2);
+    let feedback = chain.push(&first).unwrap();
+    assert_eq!(feedback.block_position, QueryRange::new(0, 1));
+    let second = block(1);
+    let feedback = chain.push(&second).unwrap();
+    assert_eq!(feedback.block_position, QueryRange::new(2, 2));
+
+    assert_eq!(chain.tip().unwrap().unwrap().get_header().height, 2);
+    assert!(chain.get_published_block_at(3).is_err());
+    assert!(chain
+        .get_records::<Transaction>(QueryRange::new(3, 3))
+        .is_err());
+
+    for (height, pushed) in [(1, &first), (2, &second)] {
+        let published = chain.get_published_block_at(height).unwrap();
+        let stored: Block<Transaction> = chain.get_block(published.get_hash()).unwrap();
+        assert_eq!(
+            stored.records_root(HashAlgorithm::Sha256).unwrap(),
+            pushed.records_root(HashAlgorithm::Sha256).unwrap()
+        );
+    }
+}
+
+#[test]
+fn failed_push_rolls_back_in_memory() {
+    push_after_failure(InMemoryDB::new());
+}
+
+#[test]
+fn failed_push_rolls_back_in_sqlite() {
+    let path = std::env::temp_dir().join(format!("atomic-{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path = path.to_str().unwrap();
+
+    push_after_failure(SqliteDB2::new(path).unwrap());
+
+    // Only the records of the two accepted blocks reached the file
+    let reopened = SqliteDB2::new(path).unwrap();
+    assert_eq!(reopened.size_of_table::<&Block<Transaction>>(), Ok(Some(3)));
+    assert_eq!(reopened.size_of_table::<&PublishedBlock>(), Ok(Some(3)));
+    fs::remove_file(path).unwrap();
+}
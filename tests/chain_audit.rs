@@ -0,0 +1,233 @@
+//! `BlockChain::verify_chain` on chains whose stored rows were altered behind its back
+
+use std::{
+    fs,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use blockchain::{
+    blockchain::{AuditMode, Block, BlockChain, ChainIssue, Genesis, Record, SignedRecord, Signer},
+    consensus::Consensus,
+    errs::CustomErrs,
+    gen,
+    io::{Database2, QueryRange},
+    utils::{SqliteDB2, Transaction},
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+const BLOCKS: u64 = 3;
+const RECORDS_PER_BLOCK: u64 = 2;
+
+fn genesis() -> Genesis {
+    Genesis::new("audit")
+}
+
+/// Path of a fresh database for `test`
+fn db_path(test: &str) -> String {
+    let path = std::env::temp_dir().join(format!("audit-{}-{}.db", test, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path.to_str().unwrap().to_owned()
+}
+
+/// Stores `BLOCKS` blocks of `RECORDS_PER_BLOCK` transactions from one signer, each record
+/// with its own amount
+fn write_chain(path: &str) {
+    let mut chain = BlockChain::open(SqliteDB2::new(path).unwrap(), genesis()).unwrap();
+    let key_pair = gen::generate_key_pair();
+
+    for height in 0..BLOCKS {
+        let signed_records = (0..RECORDS_PER_BLOCK)
+            .map(|index| {
+                let nonce = height * RECORDS_PER_BLOCK + index;
+                Transaction::new("A", "B", &nonce.to_string())
+                    .sign(key_pair.private_key(), &genesis().chain_id(), nonce)
+                    .unwrap()
+            })
+            .collect();
+        chain.push(&Block { signed_records }).unwrap();
+    }
+}
+
+/// Runs `sql` directly on the database, as something other than the chain would
+fn tamper(path: &str, sql: &str) {
+    let changed = Connection::open(path).unwrap().execute(sql, []).unwrap();
+    assert_eq!(changed, 1, "{}", sql);
+}
+
+fn audit(path: &str, mode: AuditMode) -> Vec<ChainIssue> {
+    let chain = BlockChain::open(SqliteDB2::new(path).unwrap(), genesis()).unwrap();
+    let report = chain.verify_chain::<Transaction>(mode).unwrap();
+    assert_eq!(report.is_valid(), report.issues.is_empty());
+    report.issues
+}
+
+/// Writes a chain, tampers with it and checks the issues of both audit modes
+fn assert_issues(test: &str, sql: &str, check: impl Fn(&[ChainIssue])) {
+    let path = db_path(test);
+    write_chain(&path);
+    assert_eq!(audit(&path, AuditMode::AllIssues), []);
+
+    tamper(&path, sql);
+    let issues = audit(&path, AuditMode::AllIssues);
+    check(&issues);
+
+    assert_eq!(audit(&path, AuditMode::FirstIssue), issues[..1]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn replaced_record_is_reported() {
+    // The first record of the second block gets the content of the first record on chain
+    assert_issues(
+        "record",
+        "UPDATE RECORDCHAIN SET Record = (SELECT Record FROM RECORDCHAIN WHERE Position = 1)
+         WHERE Position = 3",
+        |issues| {
+            assert!(matches!(
+                issues,
+                [
+                    ChainIssue::BadSignature {
+                        height: 2,
+                        index: 0,
+                        ..
+                    },
+                    ChainIssue::RecordsRootMismatch { height: 2 },
+                ]
+            ));
+        },
+    );
+}
+
+#[test]
+fn replaced_header_hash_is_reported() {
+    // The block at height 1 claims the hash of the block at height 2
+    assert_issues(
+        "hash",
+        "UPDATE BLOCKCHAIN SET Hash = (SELECT Hash FROM BLOCKCHAIN WHERE Position = 3)
+         WHERE Position = 2",
+        |issues| {
+            assert_eq!(
+                issues,
+                [
+                    ChainIssue::HashMismatch { height: 1 },
+                    ChainIssue::BrokenLink { height: 2 },
+                ]
+            );
+        },
+    );
+}
+
+#[test]
+fn moved_block_range_is_reported() {
+    // The last block points one record further than where its records are
+    let range = QueryRange::new(5, 6);
+    assert_issues(
+        "range",
+        &format!(
+            "UPDATE BLOCKCHAIN SET Range = '{}' WHERE Position = 4",
+            serde_json::to_string(&range).unwrap()
+        ),
+        |issues| {
+            assert_eq!(
+                issues,
+                [
+                    ChainIssue::GappedRange { height: 3, range },
+                    ChainIssue::MissingRecords { height: 3, range },
+                ]
+            );
+        },
+    );
+}
+
+#[test]
+fn changed_nonce_is_reported() {
+    // The last record of the chain claims the nonce of the one before it
+    assert_issues(
+        "nonce",
+        "UPDATE RECORDCHAIN SET Nonce = '4' WHERE Position = 6",
+        |issues| {
+            assert!(matches!(
+                issues,
+                [
+                    ChainIssue::BadSignature {
+                        height: 3,
+                        index: 1,
+                        ..
+                    },
+                    ChainIssue::NonceViolation {
+                        height: 3,
+                        index: 1,
+                        reason: CustomErrs::ReplayedNonce {
+                            expected: 5,
+                            found: 4
+                        },
+                    },
+                    ChainIssue::RecordsRootMismatch { height: 3 },
+                ]
+            ));
+        },
+    );
+}
+
+/// Whether `Ticket::validate_against` closes sales, so that the chain can first be built
+static SALES_CLOSE: AtomicBool = AtomicBool::new(false);
+
+/// Record only accepted on a chain of at most two blocks, once sales close
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Ticket {
+    seat: u64,
+}
+
+impl Record for Ticket {
+    const RECORD_TYPE: &'static str = "ticket";
+
+    fn validate_against<D: Database2, C: Consensus>(
+        &self,
+        _signer: &Signer,
+        chain: &BlockChain<D, C>,
+        _preceding: &[SignedRecord<Self>],
+    ) -> Result<(), CustomErrs> {
+        let height = chain.tip()?.map_or(0, |tip| tip.get_header().height);
+        if SALES_CLOSE.load(Ordering::Relaxed) && height >= 2 {
+            return Err(CustomErrs::RecordRejected {
+                reason: "sales are closed".to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn records_are_checked_against_the_chain_before_their_block() {
+    let path = db_path("validate-against");
+    let mut chain = BlockChain::open(SqliteDB2::new(&path).unwrap(), genesis()).unwrap();
+    let key_pair = gen::generate_key_pair();
+    for seat in 0..BLOCKS {
+        let ticket = Ticket { seat }
+            .sign(key_pair.private_key(), &genesis().chain_id(), seat)
+            .unwrap();
+        chain
+            .push(&Block {
+                signed_records: vec![ticket],
+            })
+            .unwrap();
+    }
+
+    // Only the last block was placed on a chain of two blocks
+    SALES_CLOSE.store(true, Ordering::Relaxed);
+    let report = chain.verify_chain::<Ticket>(AuditMode::AllIssues).unwrap();
+    assert_eq!(
+        report.issues,
+        [ChainIssue::RejectedRecord {
+            height: 3,
+            index: 0,
+            reason: CustomErrs::RecordRejected {
+                reason: "sales are closed".to_owned()
+            },
+        }]
+    );
+
+    drop(chain);
+    fs::remove_file(&path).unwrap();
+}
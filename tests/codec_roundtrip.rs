@@ -0,0 +1,93 @@
+//! Blocks and their headers encoded with `Codec` and read back
+
+use blockchain::{
+    blockchain::{Block, BlockHeader, Genesis, Record},
+    codec::{self, Codec},
+    errs::CustomErrs,
+    gen::{self, HashAlgorithm},
+    utils::Transaction,
+};
+
+const ALGORITHMS: [HashAlgorithm; 2] = [HashAlgorithm::Sha256, HashAlgorithm::Blake3];
+
+fn block() -> Block<Transaction> {
+    let chain_id = Genesis::new("codec").chain_id();
+    let key_pair = gen::generate_key_pair();
+    Block {
+        signed_records: (0..3)
+            .map(|nonce| {
+                Transaction::new("A", "B", &nonce.to_string())
+                    .sign(key_pair.private_key(), &chain_id, nonce)
+                    .unwrap()
+            })
+            .collect(),
+    }
+}
+
+/// Checks that `decoded` is `block` as published under `header`
+fn assert_same_block(
+    decoded: (Block<Transaction>, BlockHeader),
+    block: &Block<Transaction>,
+    header: &BlockHeader,
+    algorithm: HashAlgorithm,
+) {
+    let (decoded, decoded_header) = decoded;
+    assert_eq!(&decoded_header, header);
+    assert_eq!(decoded_header.hash(algorithm), header.hash(algorithm));
+    assert_eq!(
+        decoded.records_root(algorithm).unwrap(),
+        block.records_root(algorithm).unwrap()
+    );
+
+    let verified = decoded.verify(&decoded_header, algorithm).unwrap();
+    assert_eq!(verified.hash, header.hash(algorithm));
+}
+
+#[test]
+fn block_round_trips_through_bytes() {
+    let block = block();
+    for algorithm in ALGORITHMS {
+        let header = block.header(None, algorithm).unwrap();
+        let decoded = (
+            Block::from_bytes(&block.to_bytes().unwrap()).unwrap(),
+            BlockHeader::from_bytes(&header.to_bytes().unwrap()).unwrap(),
+        );
+        assert_same_block(decoded, &block, &header, algorithm);
+    }
+}
+
+#[test]
+fn block_round_trips_through_json() {
+    let block = block();
+    for algorithm in ALGORITHMS {
+        let header = block.header(None, algorithm).unwrap();
+        let decoded = (
+            Block::from_json(&block.to_json().unwrap()).unwrap(),
+            BlockHeader::from_json(&header.to_json().unwrap()).unwrap(),
+        );
+        assert_same_block(decoded, &block, &header, algorithm);
+    }
+}
+
+#[test]
+fn unknown_version_is_rejected() {
+    let block = block();
+    let unknown = codec::VERSION + 1;
+
+    let mut bytes = block.to_bytes().unwrap();
+    bytes[0] = unknown;
+    assert_eq!(
+        Block::<Transaction>::from_bytes(&bytes).map(|_| ()),
+        Err(CustomErrs::UnsupportedCodecVersion { found: unknown })
+    );
+
+    let json = block.to_json().unwrap().replacen(
+        &format!("\"version\":{}", codec::VERSION),
+        &format!("\"version\":{}", unknown),
+        1,
+    );
+    assert_eq!(
+        Block::<Transaction>::from_json(&json).map(|_| ()),
+        Err(CustomErrs::UnsupportedCodecVersion { found: unknown })
+    );
+}
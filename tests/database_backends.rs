@@ -0,0 +1,228 @@
+//! Every `Database2` backend must answer the same queries with the same rows
+
+use std::{fs, path::PathBuf};
+
+use blockchain::{
+    errs::CustomErrs,
+    io::{Database2, DatabaseInsertable, QueryRange},
+    utils::{InMemoryDB, LogDB, SqliteDB2},
+};
+
+/// Rows of `(name, value)` pairs, keyed by name
+#[derive(Clone, Copy)]
+struct Entries<'a>(&'a [[&'a str; 2]]);
+
+impl DatabaseInsertable for Entries<'_> {
+    fn get_name() -> &'static str {
+        "ENTRIES"
+    }
+
+    fn columns() -> &'static [&'static str] {
+        &["Name", "Value"]
+    }
+
+    fn key_column() -> Option<&'static str> {
+        Some("Name")
+    }
+
+    fn len(&self) -> i64 {
+        self.0.len() as i64
+    }
+}
+
+impl<'a> IntoIterator for Entries<'a> {
+    type Item = Result<Vec<String>, CustomErrs>;
+
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(
+            self.0
+                .iter()
+                .map(|entry| Ok(entry.iter().map(|column| column.to_string()).collect())),
+        )
+    }
+}
+
+/// A table that is never created
+#[derive(Clone, Copy)]
+struct Missing;
+
+impl DatabaseInsertable for Missing {
+    fn get_name() -> &'static str {
+        "MISSING"
+    }
+
+    fn columns() -> &'static [&'static str] {
+        &["Name"]
+    }
+
+    fn len(&self) -> i64 {
+        0
+    }
+}
+
+/// Rows of a second table, only ever written inside a rolled back transaction
+#[derive(Clone, Copy)]
+struct Other<'a>(&'a [[&'a str; 2]]);
+
+impl DatabaseInsertable for Other<'_> {
+    fn get_name() -> &'static str {
+        "OTHER"
+    }
+
+    fn columns() -> &'static [&'static str] {
+        &["Name", "Value"]
+    }
+
+    fn len(&self) -> i64 {
+        self.0.len() as i64
+    }
+}
+
+impl<'a> IntoIterator for Other<'a> {
+    type Item = Result<Vec<String>, CustomErrs>;
+
+    type IntoIter = <Entries<'a> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Entries(self.0).into_iter()
+    }
+}
+
+const FIRST: [[&str; 2]; 3] = [["a", "1"], ["b", "2"], ["c", "1"]];
+const SECOND: [[&str; 2]; 2] = [["d", "3"], ["e", "1"]];
+
+/// Path of a fresh database file or directory for `test`
+fn temp_path(backend: &str, test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "backends-{}-{}-{}",
+        backend,
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+fn sqlite(test: &str) -> SqliteDB2 {
+    SqliteDB2::new(temp_path("sqlite", test).to_str().unwrap()).unwrap()
+}
+
+fn log(test: &str) -> LogDB {
+    LogDB::open(temp_path("log", test)).unwrap()
+}
+
+fn rows(entries: &[[&str; 2]]) -> Vec<Vec<String>> {
+    Entries(entries).into_iter().map(Result::unwrap).collect()
+}
+
+/// Inserts `FIRST` then `SECOND`, returning where each one was placed
+fn fill<D: Database2>(database: &mut D) -> Vec<QueryRange> {
+    vec![
+        database.insert(&Entries(&FIRST)).unwrap(),
+        database.insert(&Entries(&SECOND)).unwrap(),
+    ]
+}
+
+/// Answers of `database` to a set of queries, including ones that fail
+fn queries<D: Database2>(database: &D) -> Vec<Result<Vec<Vec<String>>, CustomErrs>> {
+    let ranges = [(0, 4), (1, 2), (3, 10), (5, 9), (-1, 1), (2, 1), (4, 4)];
+    let mut answers: Vec<_> = ranges
+        .into_iter()
+        .map(|(begin, end)| database.get_rows::<Entries>(QueryRange::new(begin, end)))
+        .collect();
+
+    answers.extend([
+        database.get_row::<Entries>(4).map(|row| vec![row]),
+        database.get_row::<Entries>(5).map(|row| vec![row]),
+        database.find_rows::<Entries>("Name", "c"),
+        database.find_rows::<Entries>("Name", "z"),
+        database.find_rows::<Entries>("Value", "1"),
+        database.find_rows::<Entries>("Missing", "1"),
+        database.get_rows::<Missing>(QueryRange::new(0, 4)),
+        database.find_rows::<Missing>("Name", "a"),
+    ]);
+    answers
+}
+
+#[test]
+fn backends_answer_queries_alike() {
+    let mut memory = InMemoryDB::new();
+    let ranges = fill(&mut memory);
+    assert_eq!(ranges, [QueryRange::new(0, 2), QueryRange::new(3, 4)]);
+
+    let expected = queries(&memory);
+    assert_eq!(expected[0], Ok(rows(&[&FIRST[..], &SECOND].concat())));
+    assert_eq!(expected[1], Ok(rows(&FIRST[1..])));
+    assert_eq!(expected[2], Ok(rows(&SECOND)));
+    assert_eq!(expected[3], Ok(vec![]));
+    assert!(matches!(
+        expected[8],
+        Err(CustomErrs::NoSuchRowInTable { position: 5, .. })
+    ));
+    assert_eq!(expected[9], Ok(rows(&FIRST[2..])));
+    assert_eq!(expected[11], Ok(rows(&[FIRST[0], FIRST[2], SECOND[1]])));
+    assert!(matches!(
+        expected[12],
+        Err(CustomErrs::NoSuchColumnInTable { .. })
+    ));
+
+    let mut sqlite = sqlite("queries");
+    assert_eq!(fill(&mut sqlite), ranges);
+    assert_eq!(queries(&sqlite), expected);
+    assert_eq!(sqlite.size_of_table::<Entries>(), Ok(Some(5)));
+
+    let mut log = log("queries");
+    assert_eq!(fill(&mut log), ranges);
+    assert_eq!(queries(&log), expected);
+    assert_eq!(log.size_of_table::<Entries>(), Ok(Some(5)));
+
+    assert_eq!(memory.size_of_table::<Entries>(), Ok(Some(5)));
+    assert_eq!(memory.size_of_table::<Missing>(), Ok(None));
+    assert_eq!(sqlite.size_of_table::<Missing>(), Ok(None));
+    assert_eq!(log.size_of_table::<Missing>(), Ok(None));
+}
+
+/// Starts a transaction that writes to an existing and to a new table, then rolls it back
+fn roll_back<D: Database2>(database: &mut D) {
+    let before = queries(database);
+
+    database.begin().unwrap();
+    database.insert(&Entries(&[["f", "4"]])).unwrap();
+    database.insert(&Other(&[["g", "5"]])).unwrap();
+    assert_eq!(database.size_of_table::<Entries>(), Ok(Some(6)));
+    database.rollback().unwrap();
+
+    assert_eq!(queries(database), before);
+    assert_eq!(database.size_of_table::<Entries>(), Ok(Some(5)));
+    assert_eq!(database.size_of_table::<Other>(), Ok(None));
+    // Nothing is left to roll back
+    assert!(database.rollback().is_err());
+
+    // Positions given out inside the transaction are handed out again
+    let range = database
+        .transaction(|database| database.insert(&Entries(&[["h", "6"]])))
+        .unwrap();
+    assert_eq!(range, QueryRange::new(5, 5));
+    assert_eq!(
+        database.get_row::<Entries>(5),
+        Ok(rows(&[["h", "6"]]).remove(0))
+    );
+}
+
+#[test]
+fn rollback_leaves_every_table_unchanged() {
+    let mut memory = InMemoryDB::new();
+    fill(&mut memory);
+    roll_back(&mut memory);
+
+    let mut sqlite = sqlite("rollback");
+    fill(&mut sqlite);
+    roll_back(&mut sqlite);
+
+    let mut log = log("rollback");
+    fill(&mut log);
+    roll_back(&mut log);
+}
@@ -0,0 +1,139 @@
+//! Authority set changes approved by a majority of the authorities in place
+
+use blockchain::{
+    blockchain::{Block, BlockChain, BlockHeader, Genesis, Record},
+    consensus::{GovernanceRecord, ProofOfAuthority},
+    errs::CustomErrs,
+    gen::{self, KeyPair, PublicKey},
+    utils::{InMemoryDB, Transaction},
+};
+
+type Chain = BlockChain<InMemoryDB, ProofOfAuthority>;
+
+fn public_keys(key_pairs: &[&KeyPair]) -> Vec<PublicKey> {
+    key_pairs
+        .iter()
+        .map(|key_pair| *key_pair.public_key())
+        .collect()
+}
+
+fn block(chain: &Chain, nonce: u64, key_pair: &KeyPair) -> Block<Transaction> {
+    Block {
+        signed_records: vec![Transaction::new("A", "B", "1")
+            .sign(key_pair.private_key(), &chain.chain_id(), nonce)
+            .unwrap()],
+    }
+}
+
+/// Header of the next block, carrying `governance` and sealed by `sealer`
+fn sealed(
+    chain: &Chain,
+    block: &Block<Transaction>,
+    sealer: &KeyPair,
+    governance: Option<GovernanceRecord>,
+) -> BlockHeader {
+    let mut header = chain.next_header(block).unwrap();
+    header.governance = governance;
+    header.sealer = Some(*sealer.public_key());
+    header.seal = Some(sealer.sign(&header.seal_message().unwrap()));
+    header
+}
+
+/// Record replacing `current` by `new`, approved by `approvers` for the next block
+fn governance(
+    chain: &Chain,
+    current: &[PublicKey],
+    new: Vec<PublicKey>,
+    approvers: &[&KeyPair],
+) -> GovernanceRecord {
+    let parent = *chain.tip().unwrap().unwrap().get_hash();
+    let mut governance = GovernanceRecord::new(new);
+    for approver in approvers {
+        governance.approve(&chain.chain_id(), &parent, current, approver.private_key());
+    }
+    governance
+}
+
+#[test]
+fn approvals_cannot_be_replayed_once_the_set_reverts() {
+    let (a, b, d) = (
+        gen::generate_key_pair(),
+        gen::generate_key_pair(),
+        gen::generate_key_pair(),
+    );
+    let user = gen::generate_key_pair();
+    let genesis = Genesis::new("governance").with_authorities(public_keys(&[&a, &b]));
+    let mut chain =
+        BlockChain::open_with_consensus(InMemoryDB::new(), genesis, ProofOfAuthority::new())
+            .unwrap();
+
+    // Height 1, sealed by B: A and B hand the chain over to D
+    let to_d = governance(
+        &chain,
+        &public_keys(&[&a, &b]),
+        public_keys(&[&d]),
+        &[&a, &b],
+    );
+    let first = block(&chain, 0, &user);
+    chain
+        .push_with_header(sealed(&chain, &first, &b, Some(to_d.clone())), &first)
+        .unwrap();
+
+    // Height 2, sealed by D: D hands it back to A and B
+    let to_a_b = governance(&chain, &public_keys(&[&d]), public_keys(&[&a, &b]), &[&d]);
+    let second = block(&chain, 1, &user);
+    chain
+        .push_with_header(sealed(&chain, &second, &d, Some(to_a_b)), &second)
+        .unwrap();
+
+    // Height 3, sealed by B: the approvals A and B once gave to D are for another parent
+    let third = block(&chain, 2, &user);
+    assert!(matches!(
+        chain.push_with_header(sealed(&chain, &third, &b, Some(to_d)), &third),
+        Err(CustomErrs::VerificationDoesNotMatch { .. })
+    ));
+    assert_eq!(chain.tip().unwrap().unwrap().get_header().height, 2);
+
+    // The same change approved anew for this parent is accepted
+    let to_d = governance(
+        &chain,
+        &public_keys(&[&a, &b]),
+        public_keys(&[&d]),
+        &[&a, &b],
+    );
+    chain
+        .push_with_header(sealed(&chain, &third, &b, Some(to_d)), &third)
+        .unwrap();
+}
+
+#[test]
+fn changes_need_a_strict_majority() {
+    let (a, b, d) = (
+        gen::generate_key_pair(),
+        gen::generate_key_pair(),
+        gen::generate_key_pair(),
+    );
+    let user = gen::generate_key_pair();
+    let genesis = Genesis::new("governance").with_authorities(public_keys(&[&a, &b]));
+    let mut chain =
+        BlockChain::open_with_consensus(InMemoryDB::new(), genesis, ProofOfAuthority::new())
+            .unwrap();
+
+    let current = public_keys(&[&a, &b]);
+    let half = governance(&chain, &current, public_keys(&[&d]), &[&b]);
+    let first = block(&chain, 0, &user);
+    assert_eq!(
+        chain
+            .push_with_header(sealed(&chain, &first, &b, Some(half)), &first)
+            .map(|_| ()),
+        Err(CustomErrs::InsufficientGovernanceApprovals)
+    );
+
+    let outsider = governance(&chain, &current, public_keys(&[&d]), &[&a, &d]);
+    assert_eq!(
+        chain
+            .push_with_header(sealed(&chain, &first, &b, Some(outsider)), &first)
+            .map(|_| ()),
+        Err(CustomErrs::InvalidGovernanceRecord)
+    );
+}
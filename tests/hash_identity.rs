@@ -0,0 +1,56 @@
+//! A `Hash` is the same block only if its algorithm matches too, in memory and in storage
+
+use blockchain::{
+    blockchain::{Block, BlockChain, BlockHeader, Genesis, Record},
+    errs::CustomErrs,
+    gen::{self, Hash, HashAlgorithm},
+    utils::{InMemoryDB, Transaction},
+};
+
+const ALGORITHMS: [HashAlgorithm; 4] = [
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Sha512_256,
+    HashAlgorithm::Sha3_256,
+    HashAlgorithm::Blake3,
+];
+
+#[test]
+fn blocks_are_found_by_equal_hashes_only() {
+    for algorithm in ALGORITHMS {
+        let genesis = Genesis::new("identity").with_hash_algorithm(algorithm);
+        let mut chain = BlockChain::open(InMemoryDB::new(), genesis.clone()).unwrap();
+        let key_pair = gen::generate_key_pair();
+        let block = Block {
+            signed_records: vec![Transaction::new("A", "B", "1")
+                .sign(key_pair.private_key(), &genesis.chain_id(), 0)
+                .unwrap()],
+        };
+        let hash = chain.push(&block).unwrap().hash;
+        assert_eq!(hash.algorithm(), algorithm);
+
+        let parsed: Hash = hash.to_string().parse().unwrap();
+        assert_eq!(parsed, hash);
+        assert!(chain.get_block::<Transaction>(&parsed).is_ok());
+
+        // The same bytes under the default algorithm, e.g. parsed from bare hex
+        let bare = Hash::from(hash.to_bytes());
+        let found = chain.get_block::<Transaction>(&bare).map(|_| ());
+        if algorithm == HashAlgorithm::default() {
+            assert_eq!(bare, hash);
+            assert_eq!(found, Ok(()));
+        } else {
+            assert_ne!(bare, hash);
+            assert_eq!(found, Err(CustomErrs::NoSuchBlockInDatabase { hash: bare }));
+        }
+
+        let genesis_header = chain.get_published_block_at(0).unwrap();
+        assert_eq!(
+            genesis_header.get_header().previous_hash,
+            BlockHeader::null_hash(algorithm)
+        );
+        assert_eq!(
+            genesis_header.get_header().previous_hash.algorithm(),
+            algorithm
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Test vectors of SLIP-0010 (ed25519) and BIP-39
+
+use blockchain::gen::hd::{DerivationPath, ExtendedKey, Mnemonic};
+
+/// (path, chain code, private key, public key) of SLIP-0010 ed25519 test vector 1.
+///
+/// Public keys are listed without the leading `00` byte of the specification.
+const SLIP10_VECTOR_1: [(&str, &str, &str, &str); 6] = [
+    (
+        "m",
+        "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+        "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+        "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+    ),
+    (
+        "m/0H",
+        "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+        "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+        "8c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+    ),
+    (
+        "m/0H/1H",
+        "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+        "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+        "1932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+    ),
+    (
+        "m/0H/1H/2H",
+        "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+        "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+        "ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+    ),
+    (
+        "m/0H/1H/2H/2H",
+        "8f6d87f93d750e0efccda017d662a1b31a266e4a6f5993b15f5c1f07f74dd5cc",
+        "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+        "8abae2d66361c879b900d204ad2cc4984fa2aa344dd7ddc46007329ac76c429c",
+    ),
+    (
+        "m/0H/1H/2H/2H/1000000000H",
+        "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+        "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+        "3c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a",
+    ),
+];
+
+#[test]
+fn slip10_ed25519_vector_1() {
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let master = ExtendedKey::from_seed(&seed);
+
+    for (path, chain_code, private_key, public_key) in SLIP10_VECTOR_1 {
+        let key = master.derive_path(&path.parse().unwrap()).unwrap();
+        assert_eq!(hex::encode(key.chain_code()), chain_code, "{}", path);
+        assert_eq!(*key.private_key().to_hex(), private_key, "{}", path);
+        assert_eq!(
+            key.key_pair().public_key().to_string(),
+            public_key,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn derivation_is_stepwise() {
+    let master = ExtendedKey::from_seed(&[7; 32]);
+    let direct = master.derive_path(&"m/44'/0'/3'".parse().unwrap()).unwrap();
+    let stepwise = master
+        .derive_child(44)
+        .and_then(|key| key.derive_child(0))
+        .and_then(|key| key.derive_child(3))
+        .unwrap();
+
+    assert_eq!(direct.chain_code(), stepwise.chain_code());
+    assert_eq!(
+        direct.private_key().as_bytes(),
+        stepwise.private_key().as_bytes()
+    );
+    assert_eq!(
+        DerivationPath::account(0, 3).unwrap().to_string(),
+        "m/44'/0'/3'"
+    );
+}
+
+#[test]
+fn bip39_seed_vector() {
+    let mnemonic = Mnemonic::parse(
+        "abandon abandon abandon abandon abandon abandon \
+         abandon abandon abandon abandon abandon about",
+    )
+    .unwrap();
+
+    assert_eq!(
+        hex::encode(mnemonic.to_seed("TREZOR").as_ref()),
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+    );
+    assert_eq!(
+        *Mnemonic::from_entropy(&[0; 16]).unwrap().phrase(),
+        *mnemonic.phrase()
+    );
+}
+
+#[test]
+fn restoring_a_phrase_restores_every_account() {
+    let mnemonic = Mnemonic::generate(24).unwrap();
+    let restored = Mnemonic::parse(&mnemonic.phrase()).unwrap();
+
+    for account in 0..3 {
+        let path = DerivationPath::account(0, account).unwrap();
+        let original = mnemonic.master_key("").derive_path(&path).unwrap();
+        let recovered = restored.master_key("").derive_path(&path).unwrap();
+        assert_eq!(
+            original.key_pair().public_key(),
+            recovered.key_pair().public_key()
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert!(Mnemonic::generate(13).is_err());
+    assert!(Mnemonic::parse("abandon abandon abandon").is_err());
+    for path in ["", "m/", "0'", "m/0", "m/-1'", "m/2147483648'", "m/1'/x'"] {
+        assert!(path.parse::<DerivationPath>().is_err(), "{}", path);
+    }
+    assert!(ExtendedKey::from_seed(&[0; 16])
+        .derive_child(1 << 31)
+        .is_err());
+}
@@ -0,0 +1,77 @@
+//! Headers must not be older than their parent nor too far ahead of the local clock
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use blockchain::{
+    blockchain::{Block, BlockChain, Genesis, Record, DEFAULT_MAX_CLOCK_DRIFT},
+    errs::CustomErrs,
+    gen,
+    utils::{InMemoryDB, Transaction},
+};
+
+const MINUTE: u64 = 60 * 1000;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn block(nonce: u64) -> Block<Transaction> {
+    Block {
+        signed_records: vec![Transaction::new("A", "B", "1")
+            .sign(
+                gen::generate_key_pair().private_key(),
+                &Genesis::new("clock").chain_id(),
+                nonce,
+            )
+            .unwrap()],
+    }
+}
+
+#[test]
+fn headers_ahead_of_the_clock_are_rejected() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), Genesis::new("clock")).unwrap();
+    assert_eq!(chain.max_clock_drift(), DEFAULT_MAX_CLOCK_DRIFT);
+
+    let block = block(0);
+    let mut header = chain.next_header(&block).unwrap();
+    header.timestamp = now_millis() + DEFAULT_MAX_CLOCK_DRIFT + 10 * MINUTE;
+    assert!(matches!(
+        chain.push_with_header(header.clone(), &block),
+        Err(CustomErrs::TimestampInFuture { timestamp, .. }) if timestamp == header.timestamp
+    ));
+    assert_eq!(chain.tip().unwrap().unwrap().get_header().height, 0);
+
+    // Within the drift, a clock slightly behind the sealer's still accepts the block
+    header.timestamp = now_millis() + 10 * MINUTE;
+    chain.push_with_header(header, &block).unwrap();
+}
+
+#[test]
+fn clock_drift_is_configurable() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), Genesis::new("clock"))
+        .unwrap()
+        .with_max_clock_drift(MINUTE);
+
+    let first = block(0);
+    let mut header = chain.next_header(&first).unwrap();
+    header.timestamp = now_millis() + 10 * MINUTE;
+    assert!(matches!(
+        chain.push_with_header(header.clone(), &first),
+        Err(CustomErrs::TimestampInFuture { .. })
+    ));
+
+    header.timestamp = now_millis() + MINUTE / 2;
+    chain.push_with_header(header.clone(), &first).unwrap();
+
+    // A child may not go back in time either
+    let second = self::block(0);
+    let mut child = chain.next_header(&second).unwrap();
+    child.timestamp = header.timestamp - 1;
+    assert_eq!(
+        chain.push_with_header(child, &second).map(|_| ()),
+        Err(CustomErrs::TimestampBeforeParent)
+    );
+}
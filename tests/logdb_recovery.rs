@@ -0,0 +1,90 @@
+//! Reopening a `LogDB` after a crash or after damage to its segments
+
+use std::{fs, path::PathBuf};
+
+use blockchain::{
+    blockchain::{Block, BlockChain, Genesis, Record},
+    errs::CustomErrs,
+    gen,
+    utils::{LogDB, Transaction},
+};
+
+const BLOCKS: u64 = 3;
+
+/// Empty directory for the log of `test`
+fn log_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("logdb-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Writes `BLOCKS` blocks to a log in `dir` and returns the path of its only segment
+fn write_chain(dir: &PathBuf) -> PathBuf {
+    let genesis = Genesis::new("logdb");
+    let key_pair = gen::generate_key_pair();
+    let mut chain = BlockChain::open(LogDB::open(dir).unwrap(), genesis.clone()).unwrap();
+
+    for nonce in 0..BLOCKS {
+        let signed_record = Transaction::new("A", "B", "1")
+            .sign(key_pair.private_key(), &genesis.chain_id(), nonce)
+            .unwrap();
+        let block: Block<Transaction> = Block {
+            signed_records: vec![signed_record],
+        };
+        chain.push(&block).unwrap();
+    }
+
+    dir.join("segment-00000000.log")
+}
+
+fn tip_height(dir: &PathBuf) -> Result<u64, CustomErrs> {
+    let chain = BlockChain::open(LogDB::open(dir)?, Genesis::new("logdb"))?;
+    Ok(chain.tip()?.unwrap().get_header().height)
+}
+
+#[test]
+fn torn_final_frame_is_truncated() {
+    let dir = log_dir("torn");
+    let segment = write_chain(&dir);
+    let bytes = fs::read(&segment).unwrap();
+
+    // A frame whose header made it to disk but whose payload was cut short, as left by a
+    // crash while appending. The first frame of the segment starts after the magic number.
+    let mut torn = bytes.clone();
+    torn.extend_from_slice(&bytes[8..24]);
+    fs::write(&segment, &torn).unwrap();
+
+    assert_eq!(tip_height(&dir).unwrap(), BLOCKS);
+    assert_eq!(fs::read(&segment).unwrap(), bytes);
+
+    // Only part of the header made it to disk
+    let mut torn = bytes.clone();
+    torn.extend_from_slice(&bytes[8..13]);
+    fs::write(&segment, &torn).unwrap();
+
+    assert_eq!(tip_height(&dir).unwrap(), BLOCKS);
+    assert_eq!(fs::read(&segment).unwrap(), bytes);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn corrupted_frame_length_is_reported() {
+    let dir = log_dir("corrupted-length");
+    let segment = write_chain(&dir);
+
+    // Highest byte of the payload length of the first frame: the frame now seems to run
+    // past the end of the file
+    let mut bytes = fs::read(&segment).unwrap();
+    bytes[11] ^= 0xff;
+    fs::write(&segment, &bytes).unwrap();
+
+    assert!(matches!(
+        tip_height(&dir),
+        Err(CustomErrs::CorruptedLogFile { offset: 8, .. })
+    ));
+    // Nothing was truncated
+    assert_eq!(fs::read(&segment).unwrap(), bytes);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
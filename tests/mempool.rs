@@ -0,0 +1,159 @@
+//! Admission to and removal from the `MemPool`, against the state of the chain
+
+use blockchain::{
+    blockchain::{Block, BlockChain, Genesis, Record, SignedRecord, Signer},
+    errs::CustomErrs,
+    gen::{self, KeyPair},
+    mempool::MemPool,
+    utils::{InMemoryDB, Miner, Transaction},
+};
+
+fn genesis() -> Genesis {
+    Genesis::new("mempool")
+}
+
+fn transfer(key_pair: &KeyPair, amount: &str, nonce: u64) -> SignedRecord<Transaction> {
+    Transaction::new("A", "B", amount)
+        .sign(key_pair.private_key(), &genesis().chain_id(), nonce)
+        .unwrap()
+}
+
+#[test]
+fn records_the_chain_cannot_accept_are_not_admitted() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let mut mem_pool = MemPool::new(10);
+    let key_pair = gen::generate_key_pair();
+
+    let elsewhere = Transaction::new("A", "B", "1")
+        .sign(key_pair.private_key(), &Genesis::new("other").chain_id(), 0)
+        .unwrap();
+    assert!(matches!(
+        mem_pool.admit(elsewhere, &chain),
+        Err(CustomErrs::WrongChain { .. })
+    ));
+
+    chain
+        .push(&Block {
+            signed_records: vec![transfer(&key_pair, "1", 0)],
+        })
+        .unwrap();
+    assert_eq!(
+        mem_pool
+            .admit(transfer(&key_pair, "2", 0), &chain)
+            .map(|_| ()),
+        Err(CustomErrs::ReplayedNonce {
+            expected: 1,
+            found: 0
+        })
+    );
+
+    // A later nonce waits for the ones before it
+    mem_pool.admit(transfer(&key_pair, "3", 2), &chain).unwrap();
+    assert_eq!(mem_pool.len(), 1);
+}
+
+#[test]
+fn published_nonces_leave_the_pool() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let mut mem_pool = MemPool::new(10);
+    let (first, second) = (gen::generate_key_pair(), gen::generate_key_pair());
+
+    let included = transfer(&first, "1", 0);
+    let conflicting = transfer(&first, "2", 0);
+    let later = transfer(&first, "3", 1);
+    let other = transfer(&second, "4", 0);
+    for record in [&included, &conflicting, &later, &other] {
+        mem_pool.admit(record.clone(), &chain).unwrap();
+    }
+
+    let block = Block {
+        signed_records: vec![included],
+    };
+    chain.push(&block).unwrap();
+    assert_eq!(mem_pool.remove_included(&block, &chain), 2);
+
+    assert!(!mem_pool.contains(&conflicting.hash().unwrap()));
+    assert!(mem_pool.contains(&later.hash().unwrap()));
+    assert!(mem_pool.contains(&other.hash().unwrap()));
+}
+
+fn nonces(records: &[SignedRecord<Transaction>]) -> Vec<u64> {
+    records.iter().map(|record| record.nonce).collect()
+}
+
+#[test]
+fn consecutive_miner_blocks_are_accepted() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let mut mem_pool = MemPool::new(20);
+    let (first, second) = (gen::generate_key_pair(), gen::generate_key_pair());
+
+    // Arriving out of order, with the nonces of `second` after most of those of `first`
+    for nonce in [3, 1, 0, 5, 2, 4] {
+        mem_pool
+            .admit(transfer(&first, &nonce.to_string(), nonce), &chain)
+            .unwrap();
+    }
+    for nonce in [1, 0, 2] {
+        mem_pool
+            .admit(transfer(&second, &nonce.to_string(), nonce), &chain)
+            .unwrap();
+    }
+
+    for (max_size, expected) in [(4, 4), (10, 5)] {
+        let mut miner = Miner::new();
+        assert_eq!(miner.fill_from(&mem_pool, &chain, max_size), expected);
+        let block = miner.get_block().clone();
+        chain.push(&block).unwrap();
+        assert_eq!(mem_pool.remove_included(&block, &chain), expected);
+    }
+    assert!(mem_pool.is_empty());
+    assert_eq!(chain.next_nonce(&Signer::Key(*first.public_key())), 6);
+    assert_eq!(chain.next_nonce(&Signer::Key(*second.public_key())), 3);
+}
+
+#[test]
+fn batches_follow_nonces_whatever_the_scores() {
+    let chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let mut mem_pool =
+        MemPool::with_scorer(10, |record: &SignedRecord<Transaction>| record.nonce as i64);
+    let key_pair = gen::generate_key_pair();
+    for nonce in [1, 0, 3] {
+        mem_pool
+            .admit(transfer(&key_pair, "1", nonce), &chain)
+            .unwrap();
+    }
+
+    // Nonce 3 waits for nonce 2
+    assert_eq!(nonces(&mem_pool.batch(10, &chain)), [0, 1]);
+
+    // A block already holding nonce 0 is completed from nonce 1
+    let mut miner = Miner::new();
+    miner.add_to_block(transfer(&key_pair, "1", 0));
+    assert_eq!(miner.fill_from(&mem_pool, &chain, 10), 1);
+    assert_eq!(nonces(&miner.get_block().signed_records), [0, 1]);
+}
+
+#[test]
+fn records_sharing_a_nonce_are_batched_once() {
+    let mut chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let mut mem_pool = MemPool::new(10);
+    let key_pair = gen::generate_key_pair();
+    let (kept, conflicting) = (transfer(&key_pair, "1", 0), transfer(&key_pair, "2", 0));
+    mem_pool.admit(kept.clone(), &chain).unwrap();
+    mem_pool.admit(conflicting, &chain).unwrap();
+    mem_pool.admit(transfer(&key_pair, "3", 1), &chain).unwrap();
+
+    let batch = mem_pool.batch(10, &chain);
+    assert_eq!(nonces(&batch), [0, 1]);
+    assert_eq!(batch[0].hash(), kept.hash());
+
+    // Placed without telling the pool: the next admission drops the used nonce
+    chain
+        .push(&Block {
+            signed_records: batch,
+        })
+        .unwrap();
+    mem_pool.admit(transfer(&key_pair, "4", 2), &chain).unwrap();
+    assert_eq!(mem_pool.len(), 1);
+    assert_eq!(nonces(&mem_pool.batch(10, &chain)), [2]);
+}
@@ -0,0 +1,106 @@
+//! Inclusion proofs of `MerkleTree`, for trees whose levels do and do not pair up evenly
+
+use blockchain::gen::{
+    merkle::{verify_proof, MerkleTree, Side},
+    HashAlgorithm,
+};
+
+const LEAF_COUNTS: [usize; 5] = [1, 2, 3, 5, 8];
+
+fn items(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|index| format!("record {}", index))
+        .collect()
+}
+
+#[test]
+fn every_leaf_has_a_valid_proof() {
+    for count in LEAF_COUNTS {
+        let items = items(count);
+        let tree = MerkleTree::from_items(HashAlgorithm::Sha256, &items).unwrap();
+        let root = tree.root();
+
+        let parallel = MerkleTree::from_items_parallel(HashAlgorithm::Sha256, &items).unwrap();
+        assert_eq!(parallel.root(), root, "{} leaves", count);
+
+        for (index, item) in items.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(
+                verify_proof(&root, item, &proof),
+                "leaf {} of {}",
+                index,
+                count
+            );
+            // Another item is not proven by the same path
+            assert!(!verify_proof(&root, &"another record", &proof));
+        }
+        assert!(tree.proof(count).is_none());
+    }
+}
+
+#[test]
+fn tampered_sibling_is_rejected() {
+    for count in LEAF_COUNTS.into_iter().filter(|count| *count > 1) {
+        let items = items(count);
+        let tree = MerkleTree::from_items(HashAlgorithm::Sha256, &items).unwrap();
+        let root = tree.root();
+
+        for (index, item) in items.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            for level in 0..proof.siblings.len() {
+                let mut forged = proof.clone();
+                let (side, hash) = forged.siblings[level];
+                let mut bytes = hash.to_bytes();
+                bytes[0] ^= 1;
+                forged.siblings[level] = (side, bytes.into());
+                assert!(
+                    !verify_proof(&root, item, &forged),
+                    "leaf {} of {}",
+                    index,
+                    count
+                );
+
+                let mut swapped = proof.clone();
+                let side = match side {
+                    Side::Left => Side::Right,
+                    Side::Right => Side::Left,
+                };
+                swapped.siblings[level] = (side, hash);
+                assert!(
+                    !verify_proof(&root, item, &swapped),
+                    "leaf {} of {}",
+                    index,
+                    count
+                );
+            }
+
+            let mut truncated = proof.clone();
+            truncated.siblings.pop();
+            assert!(!verify_proof(&root, item, &truncated));
+        }
+    }
+}
+
+#[test]
+fn tampered_index_is_rejected() {
+    for count in LEAF_COUNTS {
+        let items = items(count);
+        let tree = MerkleTree::from_items(HashAlgorithm::Sha256, &items).unwrap();
+        let root = tree.root();
+
+        for (index, item) in items.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            for other in (0..count + 1).filter(|other| *other != index) {
+                let mut moved = proof.clone();
+                moved.index = other;
+                assert!(
+                    !verify_proof(&root, item, &moved),
+                    "leaf {} of {} claimed at {}",
+                    index,
+                    count,
+                    other
+                );
+            }
+        }
+    }
+}
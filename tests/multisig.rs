@@ -0,0 +1,183 @@
+//! Records signed by M of N keys of a `SignerSet`
+
+use blockchain::{
+    blockchain::{Block, BlockChain, Genesis, MultiSigProposal, Record, Signer, SignerSet},
+    codec::Codec,
+    errs::CustomErrs,
+    gen::{self, KeyPair},
+    utils::{InMemoryDB, Transaction},
+};
+
+fn genesis() -> Genesis {
+    Genesis::new("multisig")
+}
+
+/// Three keys and the set in which any two of them sign
+fn two_of_three() -> (Vec<KeyPair>, SignerSet) {
+    let key_pairs: Vec<_> = (0..3).map(|_| gen::generate_key_pair()).collect();
+    let public_keys = key_pairs
+        .iter()
+        .map(|key_pair| *key_pair.public_key())
+        .collect();
+    (key_pairs, SignerSet::new(public_keys, 2).unwrap())
+}
+
+fn proposal(signer_set: &SignerSet, nonce: u64) -> MultiSigProposal<Transaction> {
+    MultiSigProposal::new(
+        Transaction::new("treasury", "B", "5"),
+        signer_set.clone(),
+        &genesis().chain_id(),
+        nonce,
+    )
+}
+
+#[test]
+fn threshold_of_signatures_publishes_the_record() {
+    let (key_pairs, signer_set) = two_of_three();
+    let mut chain = BlockChain::open(InMemoryDB::new(), genesis()).unwrap();
+    let signer = Signer::Set(signer_set.clone());
+    assert_eq!(chain.next_nonce(&signer), 0);
+
+    // Passed from the holder of the last key to the holder of the first, encoded
+    let mut proposal = proposal(&signer_set, 0);
+    proposal.sign(key_pairs[2].private_key()).unwrap();
+    assert!(!proposal.is_complete());
+    let mut proposal =
+        MultiSigProposal::<Transaction>::from_bytes(&proposal.to_bytes().unwrap()).unwrap();
+    proposal.sign(key_pairs[0].private_key()).unwrap();
+    assert!(proposal.is_complete());
+
+    let indices: Vec<u8> = proposal
+        .signatures()
+        .iter()
+        .map(|(index, _)| *index)
+        .collect();
+    assert_eq!(indices, [0, 2]);
+
+    let signed_record = proposal.finish().unwrap();
+    assert!(signed_record.verify().is_ok());
+    chain
+        .push(&Block {
+            signed_records: vec![signed_record],
+        })
+        .unwrap();
+
+    // The set counts its own nonces, apart from those of its keys
+    assert_eq!(chain.next_nonce(&signer), 1);
+    let key = Signer::Key(*key_pairs[0].public_key());
+    assert_eq!(chain.next_nonce(&key), 0);
+}
+
+#[test]
+fn proposal_rejects_bad_signers() {
+    let (key_pairs, signer_set) = two_of_three();
+    let mut proposal = proposal(&signer_set, 0);
+
+    let outsider = gen::generate_key_pair();
+    assert_eq!(
+        proposal.sign(outsider.private_key()),
+        Err(CustomErrs::NotASigner {
+            public_key: *outsider.public_key()
+        })
+    );
+
+    proposal.sign(key_pairs[1].private_key()).unwrap();
+    assert_eq!(
+        proposal.sign(key_pairs[1].private_key()),
+        Err(CustomErrs::AlreadySigned { index: 1 })
+    );
+
+    // A signature over another payload, made by a key of the set
+    let other = self::proposal(&signer_set, 1);
+    let signature = gen::sign(&other.payload().unwrap(), key_pairs[0].private_key());
+    assert!(proposal.add_signature(0, signature).is_err());
+    assert_eq!(
+        proposal.add_signature(3, signature),
+        Err(CustomErrs::InvalidSignerIndex { index: 3 })
+    );
+
+    assert_eq!(
+        proposal.finish().map(|_| ()),
+        Err(CustomErrs::NotEnoughSignatures {
+            threshold: 2,
+            found: 1
+        })
+    );
+}
+
+#[test]
+fn tampered_signatures_are_rejected() {
+    let (key_pairs, signer_set) = two_of_three();
+    let mut proposal = proposal(&signer_set, 0);
+    proposal.sign(key_pairs[0].private_key()).unwrap();
+    proposal.sign(key_pairs[1].private_key()).unwrap();
+    let signed_record = proposal.finish().unwrap();
+
+    let mut dropped = signed_record.clone();
+    dropped.signatures.pop();
+    assert_eq!(
+        dropped.verify(),
+        Err(CustomErrs::NotEnoughSignatures {
+            threshold: 2,
+            found: 1
+        })
+    );
+
+    // The same key counted twice
+    let mut repeated = signed_record.clone();
+    repeated.signatures[1] = repeated.signatures[0];
+    assert_eq!(
+        repeated.verify(),
+        Err(CustomErrs::InvalidSignerIndex { index: 0 })
+    );
+
+    let mut reversed = signed_record.clone();
+    reversed.signatures.reverse();
+    assert_eq!(
+        reversed.verify(),
+        Err(CustomErrs::InvalidSignerIndex { index: 0 })
+    );
+
+    // A signature moved to the index of another key of the set
+    let mut moved = signed_record.clone();
+    moved.signatures[1].0 = 2;
+    assert!(moved.verify().is_err());
+
+    // The signature of a key alone is never valid for a record of its set
+    let single = Transaction::new("treasury", "B", "5")
+        .sign(key_pairs[1].private_key(), &genesis().chain_id(), 0)
+        .unwrap();
+    let mut borrowed = signed_record;
+    borrowed.signatures[1] = (1, single.signatures[0].1);
+    assert!(borrowed.verify().is_err());
+}
+
+#[test]
+fn invalid_signer_sets_are_rejected() {
+    let (key_pairs, signer_set) = two_of_three();
+    let public_keys = signer_set.signers().to_vec();
+
+    assert_eq!(
+        SignerSet::new(public_keys.clone(), 0),
+        Err(CustomErrs::InvalidSignerSet {
+            signers: 3,
+            threshold: 0
+        })
+    );
+    assert_eq!(
+        SignerSet::new(public_keys.clone(), 4),
+        Err(CustomErrs::InvalidSignerSet {
+            signers: 3,
+            threshold: 4
+        })
+    );
+
+    let mut duplicated = public_keys;
+    duplicated.push(*key_pairs[1].public_key());
+    assert_eq!(
+        SignerSet::new(duplicated, 2),
+        Err(CustomErrs::DuplicateSigner {
+            public_key: *key_pairs[1].public_key()
+        })
+    );
+}
@@ -0,0 +1,197 @@
+//! `ValidationPool` must reject exactly the blocks `Block::verify` rejects, with the same error
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use blockchain::{
+    blockchain::{Block, BlockChain, BlockHeader, Genesis, Record, SignedRecord, ValidationPool},
+    errs::CustomErrs,
+    gen::{self, HashAlgorithm},
+    utils::{LogDB, Transaction},
+};
+
+const CHUNK_LEN: usize = ValidationPool::CHUNK_LEN;
+/// Enough records for two chunks, the second one short
+const RECORDS: usize = CHUNK_LEN + 8;
+const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+fn genesis() -> Genesis {
+    Genesis::new("parallel-verdicts")
+}
+
+/// `RECORDS` valid transactions from a single signer, signed once for every test
+fn signed_records() -> Vec<SignedRecord<Transaction>> {
+    static RECORDS_CELL: OnceLock<Vec<SignedRecord<Transaction>>> = OnceLock::new();
+    RECORDS_CELL
+        .get_or_init(|| {
+            let chain_id = genesis().chain_id();
+            let key_pair = gen::generate_key_pair();
+            (0..RECORDS as u64)
+                .map(|nonce| {
+                    Transaction::new("A", "B", "1")
+                        .sign(key_pair.private_key(), &chain_id, nonce)
+                        .unwrap()
+                })
+                .collect()
+        })
+        .clone()
+}
+
+/// Changes the signed payload of the record at `index`, so its signature no longer matches
+fn forge(records: &mut [SignedRecord<Transaction>], index: usize) {
+    records[index].nonce += RECORDS as u64;
+}
+
+/// Replaces the record at `index` with a correctly signed one that `validate()` rejects
+fn reject(records: &mut [SignedRecord<Transaction>], index: usize) {
+    let key_pair = gen::generate_key_pair();
+    records[index] = Transaction::new("A", "B", "banana")
+        .sign(key_pair.private_key(), &genesis().chain_id(), 0)
+        .unwrap();
+}
+
+/// Asserts that `block` is rejected under `header`, in the same way by every path
+fn assert_same_verdict(block: &Block<Transaction>, header: &BlockHeader, expected: CustomErrs) {
+    let sequential = block
+        .verify(header, ALGORITHM)
+        .map(|verified| verified.hash);
+    assert_eq!(sequential, Err(expected.clone()));
+
+    // Two workers, so that both chunks are checked at once
+    let pool = ValidationPool::new(2).unwrap();
+    let parallel = pool
+        .verify_block(block, header, ALGORITHM)
+        .map(|verified| verified.hash);
+    assert_eq!(parallel, sequential);
+    assert_eq!(import(block, header, pool), expected.in_block(1));
+}
+
+/// Imports a valid block followed by `block` under a copy of `header` placed on top of it.
+///
+/// Returns the error of the import, after checking that the valid block was placed.
+fn import(block: &Block<Transaction>, header: &BlockHeader, pool: ValidationPool) -> CustomErrs {
+    let dir = log_dir();
+    let mut chain = BlockChain::open(LogDB::open(&dir).unwrap(), genesis())
+        .unwrap()
+        .with_validation_pool(pool);
+
+    let key_pair = gen::generate_key_pair();
+    let first = Block {
+        signed_records: vec![Transaction::new("A", "B", "1")
+            .sign(key_pair.private_key(), &chain.chain_id(), 0)
+            .unwrap()],
+    };
+    let first_header = chain.next_header(&first).unwrap();
+    let mut second_header = header.clone();
+    second_header.previous_hash = first_header.hash(ALGORITHM);
+    second_header.height = first_header.height + 1;
+    second_header.timestamp = first_header.timestamp;
+
+    let err = chain
+        .import(&[(first_header, first), (second_header, block.clone())])
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(chain.tip().unwrap().unwrap().get_header().height, 1);
+
+    drop(chain);
+    fs::remove_dir_all(&dir).unwrap();
+    err
+}
+
+/// Empty directory for the log of one chain, distinct from those of concurrent tests
+fn log_dir() -> PathBuf {
+    static CHAINS: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "parallel-verdicts-{}-{}",
+        std::process::id(),
+        CHAINS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// The error `Block::verify` reports for a bad signature at `index`
+fn signature_error(block: &Block<Transaction>, index: usize) -> CustomErrs {
+    block.signed_records[index]
+        .verify()
+        .unwrap_err()
+        .in_record(index)
+}
+
+#[test]
+fn bad_signatures_around_chunk_boundaries() {
+    for index in [0, CHUNK_LEN - 1, CHUNK_LEN, RECORDS - 1] {
+        let mut signed_records = signed_records();
+        forge(&mut signed_records, index);
+        let block = Block { signed_records };
+        let header = block.header(None, ALGORITHM).unwrap();
+        assert_same_verdict(&block, &header, signature_error(&block, index));
+    }
+}
+
+#[test]
+fn first_bad_signature_is_reported() {
+    // Two bad records in different chunks: the one in the later chunk may be found first
+    let mut signed_records = signed_records();
+    forge(&mut signed_records, CHUNK_LEN - 1);
+    forge(&mut signed_records, CHUNK_LEN + 1);
+    let block = Block { signed_records };
+    let header = block.header(None, ALGORITHM).unwrap();
+    assert_same_verdict(&block, &header, signature_error(&block, CHUNK_LEN - 1));
+}
+
+#[test]
+fn rejected_records_around_chunk_boundary() {
+    for index in [CHUNK_LEN - 1, CHUNK_LEN] {
+        let mut signed_records = signed_records();
+        reject(&mut signed_records, index);
+        let block = Block { signed_records };
+        let header = block.header(None, ALGORITHM).unwrap();
+        let expected = block.signed_records[index]
+            .get_record()
+            .validate()
+            .unwrap_err()
+            .in_record(index);
+        assert_same_verdict(&block, &header, expected);
+    }
+
+    // Signatures are all checked before any record is validated
+    let mut signed_records = signed_records();
+    reject(&mut signed_records, 10);
+    forge(&mut signed_records, CHUNK_LEN + 2);
+    let block = Block { signed_records };
+    let header = block.header(None, ALGORITHM).unwrap();
+    assert_same_verdict(&block, &header, signature_error(&block, CHUNK_LEN + 2));
+}
+
+#[test]
+fn header_mismatches() {
+    let block = Block {
+        signed_records: signed_records(),
+    };
+    let header = block.header(None, ALGORITHM).unwrap();
+
+    let mut wrong_count = header.clone();
+    wrong_count.record_count += 1;
+    let expected = block
+        .verify(&wrong_count, ALGORITHM)
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(expected, CustomErrs::RecordCountMismatch { .. }));
+    assert_same_verdict(&block, &wrong_count, expected);
+
+    let mut wrong_root = header;
+    wrong_root.records_root = gen::digest(b"another block");
+    let expected = block
+        .verify(&wrong_root, ALGORITHM)
+        .map(|_| ())
+        .unwrap_err();
+    assert!(matches!(expected, CustomErrs::RecordsRootMismatch));
+    assert_same_verdict(&block, &wrong_root, expected);
+}
@@ -0,0 +1,117 @@
+//! Single and batch verification agree on every signature, and parsing only checks encodings
+
+use blockchain::gen::{self, PublicKey, Signature};
+use curve25519_dalek::{
+    constants::{BASEPOINT_ORDER, ED25519_BASEPOINT_POINT, EIGHT_TORSION},
+    scalar::Scalar,
+};
+use rand::Rng;
+use sha2::{Digest, Sha512};
+
+fn random_scalar() -> Scalar {
+    Scalar::from_bytes_mod_order(rand::thread_rng().gen())
+}
+
+/// A key with a small-order component and signatures of `messages` by its secret scalar.
+///
+/// Its signatures only pass a cofactorless check when `k` is a multiple of 8.
+fn torsioned_signatures(messages: &[Vec<u8>]) -> (PublicKey, Vec<Signature>) {
+    let secret = random_scalar();
+    let point = ED25519_BASEPOINT_POINT * secret + EIGHT_TORSION[1];
+    let public_key = PublicKey::from_bytes(point.compress().as_bytes()).unwrap();
+
+    let signatures = messages
+        .iter()
+        .map(|msg| {
+            let nonce = random_scalar();
+            let r = (ED25519_BASEPOINT_POINT * nonce).compress();
+            let mut hash = [0; 64];
+            hash.copy_from_slice(
+                &Sha512::new()
+                    .chain_update(r.as_bytes())
+                    .chain_update(public_key.as_bytes())
+                    .chain_update(msg)
+                    .finalize(),
+            );
+            let k = Scalar::from_bytes_mod_order_wide(&hash);
+            let s = nonce + k * secret;
+            Signature::from_bytes(&[r.to_bytes(), s.to_bytes()].concat()).unwrap()
+        })
+        .collect();
+    (public_key, signatures)
+}
+
+#[test]
+fn torsioned_keys_verify_alike_one_by_one_and_in_batch() {
+    let messages: Vec<Vec<u8>> = (0..16u8).map(|index| vec![index; 3]).collect();
+    let (public_key, signatures) = torsioned_signatures(&messages);
+
+    for (msg, signature) in messages.iter().zip(&signatures) {
+        assert_eq!(public_key.verify(msg, signature), Ok(()));
+        assert_eq!(
+            gen::verify_batch(&[public_key], &[msg], &[*signature]),
+            Ok(())
+        );
+
+        let other = [msg.as_slice(), b"!"].concat();
+        assert!(public_key.verify(&other, signature).is_err());
+        assert!(gen::verify_batch(&[public_key], &[&other], &[*signature]).is_err());
+    }
+
+    let public_keys = vec![public_key; messages.len()];
+    let slices: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+    assert_eq!(
+        gen::verify_batch(&public_keys, &slices, &signatures),
+        Ok(())
+    );
+
+    let mut swapped = signatures.clone();
+    swapped.swap(0, 1);
+    assert!(gen::verify_batch(&public_keys, &slices, &swapped).is_err());
+}
+
+#[test]
+fn only_canonical_encodings_are_parsed() {
+    // y = p, p - 1 with a negative x, 1 with a negative x and 2^255 - 1
+    let mut p = [0xff; 32];
+    p[0] = 0xed;
+    p[31] = 0x7f;
+    let mut minus_one = p;
+    minus_one[0] = 0xec;
+    minus_one[31] |= 0x80;
+    let mut one = [0; 32];
+    one[0] = 1;
+    one[31] = 0x80;
+    let mut all_set = [0xff; 32];
+    all_set[31] = 0x7f;
+
+    for bytes in [p, minus_one, one, all_set] {
+        assert!(PublicKey::from_bytes(&bytes).is_err());
+        assert!(Signature::from_bytes(&[bytes, [0; 32]].concat()).is_err());
+    }
+    minus_one[31] &= 0x7f;
+    one[31] = 0;
+    for bytes in [minus_one, one] {
+        assert!(PublicKey::from_bytes(&bytes).is_ok());
+    }
+    assert!(PublicKey::from_bytes(&[1; 31]).is_err());
+
+    // A canonical encoding that is not a point is only rejected when verifying
+    let not_a_point = (2u8..)
+        .map(|y| [y; 32])
+        .find(|bytes| {
+            curve25519_dalek::edwards::CompressedEdwardsY(*bytes)
+                .decompress()
+                .is_none()
+        })
+        .unwrap();
+    let key_pair = gen::generate_key_pair();
+    let signature = key_pair.sign(b"msg");
+    let public_key = PublicKey::from_bytes(&not_a_point).unwrap();
+    assert!(public_key.verify(b"msg", &signature).is_err());
+    assert_eq!(key_pair.public_key().verify(b"msg", &signature), Ok(()));
+
+    // The scalar must be reduced
+    let unreduced = [&signature.as_bytes()[..32], BASEPOINT_ORDER.as_bytes()].concat();
+    assert!(Signature::from_bytes(&unreduced).is_err());
+}